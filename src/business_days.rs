@@ -0,0 +1,66 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use std::collections::HashSet;
+
+/// True when `t` falls on a weekday (UTC) that is not one of `holidays`
+pub(crate) fn is_business_day(t: DateTime<Utc>, holidays: Option<&HashSet<NaiveDate>>) -> bool {
+    // ---
+    !matches!(t.weekday(), Weekday::Sat | Weekday::Sun)
+        && !holidays.is_some_and(|holidays| holidays.contains(&t.date_naive()))
+}
+
+/// Moves `start` by `days` business days (backwards when negative), skipping weekends
+/// and `holidays`. The time of day is preserved; stops early at the representable extremes.
+pub(crate) fn add_business_days(
+    start: DateTime<Utc>,
+    days: i64,
+    holidays: Option<&HashSet<NaiveDate>>,
+) -> DateTime<Utc> {
+    // ---
+    let step = Duration::days(days.signum());
+    let mut t = start;
+    let mut remaining = days.abs();
+    while remaining > 0 {
+        let Some(next) = t.checked_add_signed(step) else {
+            break;
+        };
+        t = next;
+        if is_business_day(t, holidays) {
+            remaining -= 1;
+        }
+    }
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{ensure, Result};
+
+    #[test]
+    fn test_add_business_days_skips_weekends() -> Result<()> {
+        // ---
+        let wednesday = DateTime::parse_from_rfc3339("2025-07-09T12:00:00Z")?.with_timezone(&Utc);
+
+        let back = add_business_days(wednesday, -7, None);
+        ensure!(back.to_rfc3339() == "2025-06-30T12:00:00+00:00", "Unexpected {back}");
+
+        let forward = add_business_days(wednesday, 3, None);
+        ensure!(forward.to_rfc3339() == "2025-07-14T12:00:00+00:00", "Unexpected {forward}");
+
+        ensure!(add_business_days(wednesday, 0, None) == wednesday, "Expected a no-op for 0 days");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_holidays_are_skipped() -> Result<()> {
+        // ---
+        let wednesday = DateTime::parse_from_rfc3339("2025-07-09T12:00:00Z")?.with_timezone(&Utc);
+        let holidays: HashSet<NaiveDate> = ["2025-07-04".parse()?].into();
+
+        let back = add_business_days(wednesday, -7, Some(&holidays));
+        ensure!(back.to_rfc3339() == "2025-06-27T12:00:00+00:00", "Unexpected {back}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+// Clock sources.
+//
+// The filter functions take `now` explicitly; callers obtain it from a `Clock` so the
+// Lambda entry point reads the system time while tests and simulations pin it.
+
+use chrono::{DateTime, Utc};
+
+/// Source of the current time for a filter run
+pub trait Clock {
+    /// The instant the windows are measured from
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Reads the system clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    // ---
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always reports the same instant, for deterministic tests and replays
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    // ---
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FilterConfig;
+    use crate::domain::Action;
+    use crate::filter::process_actions;
+    use anyhow::{ensure, Result};
+    use chrono::Duration;
+
+    #[test]
+    fn test_pipeline_is_deterministic_under_fixed_clock() -> Result<()> {
+        // ---
+        let clock = FixedClock(DateTime::parse_from_rfc3339("2025-06-28T00:00:00Z")?.into());
+        let action = |id: &str, last_days: i64, next_days: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: clock.now() - Duration::days(last_days),
+            next_action_time: clock.now() + Duration::days(next_days),
+            ..Default::default()
+        };
+        let input = vec![action("kept", 10, 5), action("recent", 2, 5), action("far", 10, 120)];
+
+        let config = FilterConfig::default();
+        let first = process_actions(input.clone(), clock.now(), &config);
+        let second = process_actions(input, clock.now(), &config);
+        ensure!(first == second, "Expected identical runs, got {first:?} and {second:?}");
+        let ids: Vec<&str> = first.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["kept"], "Unexpected survivors {ids:?}");
+
+        Ok(())
+    }
+}
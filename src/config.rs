@@ -0,0 +1,756 @@
+use crate::domain::Priority;
+use crate::error::FilterError;
+use crate::timestamp;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Environment variable naming a JSON file to load the config from
+pub const CONFIG_FILE_ENV: &str = "ACTION_FILTER_CONFIG_FILE";
+
+/// Environment variable holding the config as inline JSON
+pub const CONFIG_JSON_ENV: &str = "ACTION_FILTER_CONFIG";
+
+/// Days ahead of `now` a `next_action_time` may fall and still be kept
+pub const DEFAULT_FUTURE_WINDOW_DAYS: i64 = 90;
+
+/// Days a `last_action_time` must lie behind `now` for the action to be kept
+pub const DEFAULT_PAST_WINDOW_DAYS: i64 = 7;
+
+/// Responses shorter than this many bytes are returned uncompressed even when gzip is accepted
+pub const DEFAULT_GZIP_MIN_BYTES: usize = 1024;
+
+/// Strategy used to pick a survivor when several actions share an entity_id
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupStrategy {
+    /// Keep the last occurrence seen in the input
+    #[default]
+    LastSeen,
+    /// Keep the occurrence with the most populated optional fields,
+    /// falling back to the last occurrence on ties
+    MostComplete,
+    /// Keep the last occurrence, carrying the union of every occurrence's tags
+    /// (in order of first appearance)
+    Merge,
+    /// Keep the occurrence ranked first by `priority_order` (Urgent by default), falling
+    /// back to the last occurrence on ties
+    HighestPriority,
+    /// Keep the occurrence due soonest (minimum next_action_time per `Action`'s `Ord`),
+    /// falling back to the last occurrence on ties
+    EarliestNext,
+    /// Fold every occurrence into the last one: the union of all tags (in order of first
+    /// appearance), the earliest next_action_time, the latest last_action_time and the
+    /// most urgent priority; other fields come from the last occurrence
+    SmartMerge,
+}
+
+/// Which of several equally urgent duplicates `DedupStrategy::HighestPriority` keeps
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriorityTieBreak {
+    /// The last occurrence seen in the input
+    #[default]
+    LastSeen,
+    /// The occurrence due soonest, then the last occurrence among those due together
+    EarliestNext,
+}
+
+/// Key that decides whether two actions are duplicates of each other
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DedupKey {
+    /// Actions sharing an entity_id are duplicates
+    #[default]
+    EntityId,
+    /// Actions whose named fields hash to the same value are duplicates.
+    /// Fields are looked up by their serialized name; missing fields hash as null.
+    ContentHash { fields: Vec<String> },
+    /// Only records identical in every serialized field (other than `metadata`) are duplicates
+    WholeRecord,
+}
+
+/// What to do with a `last_action_time` further in the future than the allowed skew
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkewPolicy {
+    /// Drop the action
+    #[default]
+    Reject,
+    /// Pull `last_action_time` back to `now`
+    Clamp,
+}
+
+/// Unit timestamps are truncated to before the window comparisons
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Second,
+    Minute,
+    Hour,
+    /// Compare calendar dates (UTC), the historical behaviour
+    #[default]
+    Day,
+}
+
+impl Granularity {
+    /// Length of one unit in seconds
+    fn seconds(self) -> i64 {
+        // ---
+        match self {
+            Granularity::Second => 1,
+            Granularity::Minute => 60,
+            Granularity::Hour => 60 * 60,
+            Granularity::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// Truncates `t` down to the start of its unit (UTC)
+    pub fn truncate(self, t: DateTime<Utc>) -> DateTime<Utc> {
+        // ---
+        let secs = t.timestamp();
+        let truncated = secs - secs.rem_euclid(self.seconds());
+        DateTime::from_timestamp(truncated, 0).unwrap_or(t)
+    }
+}
+
+/// How the keys of a map-shaped payload (`{"entity_1": {...}}`) relate to entity_id
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MapKeyPolicy {
+    /// Use the key only when the action has no entity_id of its own
+    #[default]
+    FillMissing,
+    /// Always use the key as the entity_id
+    Override,
+}
+
+/// Treatment of actions whose last_action_time equals their next_action_time
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ZeroDurationPolicy {
+    #[default]
+    Keep,
+    Drop,
+}
+
+/// Whether an action whose next_action_time is exactly `now` is already due.
+/// Either way it passes the window filters; this only decides which side of `now` it is on.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AtNowPolicy {
+    #[default]
+    Due,
+    Upcoming,
+}
+
+/// Treatment of actions without a score when `min_score` is set
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingScorePolicy {
+    #[default]
+    Keep,
+    Drop,
+}
+
+/// Treatment of actions with an empty (or whitespace-only) entity_id
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyIdPolicy {
+    /// Drop the action and count it as a validation failure
+    #[default]
+    Skip,
+    /// Fail the whole batch (enforced by `try_process_actions`)
+    Error,
+}
+
+/// How a `{"batches": [...]}` request reports sub-batches that failed
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchErrorPolicy {
+    /// Put the failed batch's problem+json in its slot of `batches`
+    #[default]
+    Inline,
+    /// Reject the whole request with the first failed batch's error
+    FailAll,
+    /// Leave failed batches out of `batches` (logged)
+    SkipBatch,
+    /// Return `{"results": [...], "errors": [...]}`: parallel arrays holding each batch's
+    /// body or problem+json, with null in the other array
+    ReportPerBatch,
+}
+
+/// Whether an empty batch of actions is a valid request
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyInputPolicy {
+    /// Return an empty result
+    #[default]
+    Allow,
+    /// Fail with `empty_input` (enforced by `try_process_actions`)
+    Error,
+}
+
+/// Treatment of actions whose entity_id does not match `entity_id_pattern`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MalformedIdPolicy {
+    /// Drop the action and count it as a validation failure
+    #[default]
+    Skip,
+    /// Fail the whole batch (enforced by `try_process_actions`)
+    Error,
+}
+
+/// What to do with an entity_id longer than `max_entity_id_len`
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthPolicy {
+    /// Drop the action and count it as a validation failure
+    #[default]
+    Reject,
+    /// Cut the entity_id down to the limit (logged)
+    Truncate,
+}
+
+/// What to do with a record whose next_action_time cannot be parsed although its
+/// last_action_time can
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NextTimeFallback {
+    /// Reject the request like any other invalid record
+    #[default]
+    Fail,
+    /// Drop the record and log why
+    Drop,
+    /// Replace next_action_time with this sentinel and log it
+    Substitute(DateTime<Utc>),
+}
+
+/// What to do with a record that has no (or a null) last_action_time
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingLastTimePolicy {
+    /// Reject the request like any other invalid record
+    #[default]
+    Require,
+    /// Treat the entity as never acted upon: last_action_time becomes the Unix epoch,
+    /// so the record passes the staleness window as if infinitely stale
+    NeverActed,
+    /// Drop the record and log why
+    Drop,
+}
+
+/// How many invalid records are reported before a request is rejected
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationMode {
+    /// Examine every record and report all failures together
+    #[default]
+    CollectAll,
+    /// Stop at the first invalid record and report only it
+    FailFast,
+}
+
+/// Whether per-record validation failures drop records
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationAction {
+    /// Drop (or reject the request for) invalid records per the individual policies
+    #[default]
+    Enforce,
+    /// Log each issue as a warning and keep the record unmodified
+    WarnOnly,
+}
+
+/// Order in which surviving actions are returned
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Urgent before Normal
+    #[default]
+    Priority,
+    /// Earliest next_action_time first. Times are compared at `comparison_granularity`;
+    /// ties are broken by entity_id.
+    NextActionTime,
+    /// Latest next_action_time first, with the same granularity and tie-break
+    NextActionTimeDesc,
+    /// Highest score first (unscored actions last), then Urgent before Normal
+    Score,
+    /// The order a `BinaryHeap` min-queue on `Action`'s `Ord` (next_action_time) pops them
+    HeapOrder,
+}
+
+/// How actions that compare equal under the sort key are ordered
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// Keep their relative input order
+    #[default]
+    InputOrder,
+    /// Order by a hash of (entity_id, seed): shuffled, but reproducible per seed
+    Hashed { seed: u64 },
+    /// Most recently acted-upon first (descending last_action_time), then input order
+    LastActionDesc,
+}
+
+/// Deterministic sampling of survivors for A/B experiments
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SampleConfig {
+    /// Share of survivors to keep, from 0.0 to 1.0
+    pub fraction: f64,
+    /// Seed mixed into each entity_id's hash; the same seed always picks the same subset
+    pub seed: u64,
+}
+
+/// Output budget that sheds whole priority tiers rather than truncating
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AdaptivePriority {
+    /// The tiers sorting last (per `priority_order`) are dropped while more survivors than
+    /// this remain (the first tier is always kept, even when it alone exceeds the budget)
+    pub max_output: usize,
+}
+
+/// Tunable behaviour for `process_actions`
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct FilterConfig {
+    /// How duplicate entity_ids are resolved
+    pub dedup_strategy: DedupStrategy,
+    /// Survivor among same-priority duplicates under `DedupStrategy::HighestPriority`
+    pub priority_tie_break: PriorityTieBreak,
+    /// What makes two actions duplicates
+    pub dedup_by: DedupKey,
+    /// Compare entity_ids case-insensitively under `DedupKey::EntityId`
+    /// (the survivor keeps its original casing)
+    pub case_insensitive_ids: bool,
+    /// Records whose last_action_time is more than this many days old are deduplicated
+    /// only among themselves, so they never suppress a fresher record (None disables)
+    pub dedup_recency_days: Option<i64>,
+    /// Keep up to this many occurrences per dedup key instead of one, choosing the ones
+    /// `dedup_strategy` ranks highest (Merge ranks like LastSeen, without the tag union).
+    /// `Some(1)` matches plain deduplication; None disables.
+    pub per_entity_limit: Option<usize>,
+    /// Maximum minutes `last_action_time` may be ahead of `now` (None disables the check)
+    pub max_future_skew_minutes: Option<i64>,
+    /// Handling of actions exceeding `max_future_skew_minutes`
+    pub future_skew_policy: SkewPolicy,
+    /// Unit timestamps are truncated to before the window comparisons
+    pub comparison_granularity: Granularity,
+    /// Tolerance in seconds at both window boundaries: an action within this much of a
+    /// boundary is treated as inside the window (0 is exact; negative values count as 0)
+    pub epsilon_seconds: i64,
+    /// Count the past and future windows in weekdays only
+    pub use_business_days: bool,
+    /// Dates (UTC) also skipped when counting business days
+    pub holidays: Option<HashSet<NaiveDate>>,
+    /// How map-shaped payload keys populate entity_id
+    pub map_key_policy: MapKeyPolicy,
+    /// After deduplication, same-entity actions whose next_action_time are within this
+    /// many seconds of each other collapse into the higher-priority one (None disables)
+    pub near_duplicate_window_secs: Option<i64>,
+    /// Order of the returned actions
+    pub sort_key: SortKey,
+    /// Explicit priority ranking for the sorts and duplicate resolution, most important first,
+    /// e.g. `["normal", "urgent"]`
+    /// (empty keeps Urgent before Normal; unlisted priorities rank after listed ones)
+    pub priority_order: Vec<Priority>,
+    /// Rewrite the priority of any action ranked below this tier to the tier itself, before
+    /// dedup and sorting, e.g. to fold minor tiers into one "other" bucket (None disables)
+    pub collapse_below: Option<Priority>,
+    /// Priority forced onto every action of an entity_id (e.g. `{"entity_5": "urgent"}`);
+    /// changed records are listed in the report's `override_audit`
+    pub priority_overrides: HashMap<String, Priority>,
+    /// Order among actions the sort key ranks equal (the next_action_time keys already
+    /// fall back to entity_id, and `HeapOrder` always uses input order)
+    pub tie_break: TieBreak,
+    /// Turns the priority sort into a score: Urgent outweighs Normal by 1.0 and each day
+    /// by which one last_action_time is more recent than another adds this much (None
+    /// keeps the plain priority order)
+    pub recency_boost: Option<f64>,
+    /// Whether a next_action_time exactly at `now` counts as due in `partition_due`
+    pub next_at_now: AtNowPolicy,
+    /// Whether actions with identical last and next action times are kept
+    pub zero_duration_policy: ZeroDurationPolicy,
+    /// Drop actions whose score is below this (None disables the check)
+    pub min_score: Option<f64>,
+    /// Whether actions without a score pass the `min_score` check
+    pub missing_score_policy: MissingScorePolicy,
+    /// Keep only a seeded, reproducible fraction of survivors (None keeps all)
+    pub sample: Option<SampleConfig>,
+    /// Fail the request when fewer actions survive than this (None disables the guard)
+    pub min_expected_survivors: Option<usize>,
+    /// Maximum number of Urgent actions returned (None is unlimited)
+    pub top_urgent: Option<usize>,
+    /// Maximum number of Normal actions returned (None is unlimited)
+    pub top_normal: Option<usize>,
+    /// Drop the lowest priority tiers while the output exceeds a budget (None disables)
+    pub adaptive_priority: Option<AdaptivePriority>,
+    /// Log a debug line for every occurrence dropped by deduplication
+    pub log_dedup_details: bool,
+    /// Soft budget for the filter loop; once exceeded, the records processed so far
+    /// are returned and the report is flagged as truncated (None disables)
+    pub deadline_ms: Option<u64>,
+    /// Whether an empty input batch is returned as-is or rejected as a caller bug
+    pub empty_input_policy: EmptyInputPolicy,
+    /// Treatment of actions with an empty entity_id
+    pub empty_id_policy: EmptyIdPolicy,
+    /// Maximum entity_id length in characters (None is unlimited)
+    pub max_entity_id_len: Option<usize>,
+    /// Handling of entity_ids exceeding `max_entity_id_len`
+    pub entity_id_len_policy: LengthPolicy,
+    /// Whether request-rejecting failures stop at the first bad record
+    pub validation_mode: ValidationMode,
+    /// Whether validation failures are acted upon or only logged, e.g. while migrating
+    pub validation_action: ValidationAction,
+    /// Handling of records with a valid last_action_time but a garbage next_action_time
+    pub next_time_fallback: NextTimeFallback,
+    /// Handling of records without a last_action_time, e.g. brand-new entities
+    pub missing_last_time_policy: MissingLastTimePolicy,
+    /// Vendor priority terms (e.g. "p1", "critical") mapped onto `Priority` before
+    /// deserialization, matched ignoring case; unmapped unknown terms still fail
+    pub priority_aliases: HashMap<String, Priority>,
+    /// UTC offset (e.g. "-05:00") assumed for timestamps sent without one (None is UTC)
+    pub assumed_utc_offset: Option<String>,
+    /// Time of day (e.g. "09:00:00", in the assumed zone) given to date-only timestamps
+    /// like "2025-07-01" (None is midnight)
+    pub date_only_time: Option<NaiveTime>,
+    /// Warn when more than this many input actions share one next_action_time,
+    /// a common sign of a bulk-import bug (None disables the check)
+    pub shared_next_time_warn_threshold: Option<usize>,
+    /// Regex (unanchored; use `^...$` for whole-id matches) whose matching entity_ids
+    /// are dropped before the window filters
+    pub deny_pattern: Option<String>,
+    /// Regex every non-empty entity_id must match (e.g. `^[a-z0-9_]+$`); unlike
+    /// `deny_pattern` this is a format requirement, so misses are validation failures
+    pub entity_id_pattern: Option<String>,
+    /// Handling of entity_ids that do not match `entity_id_pattern`
+    pub malformed_id_policy: MalformedIdPolicy,
+    /// Wrap responses as `{"schema_version": N, "actions": [...]}` (off for compatibility)
+    pub response_version: bool,
+    /// How many worker threads filter the sub-batches of a `{"batches": [...]}` request,
+    /// capped at the available CPUs (None processes them one at a time on the calling thread)
+    pub batch_concurrency: Option<usize>,
+    /// How sub-batches that fail are reported
+    pub batch_error_policy: BatchErrorPolicy,
+    /// Reject requests whose payload, serialized as compact JSON, exceeds this many bytes
+    /// (None disables the check; Lambda's synchronous limit is 6 MB)
+    pub max_payload_bytes: Option<usize>,
+    /// Smallest response, in bytes, gzipped for requests that accept gzip
+    /// (None uses `DEFAULT_GZIP_MIN_BYTES`)
+    pub gzip_min_bytes: Option<usize>,
+    /// Number of leading output actions logged at info level per invocation (0 disables)
+    pub log_sample_size: usize,
+}
+
+impl FilterConfig {
+    /// Loads the config from `ACTION_FILTER_CONFIG_FILE`, falling back to inline
+    /// `ACTION_FILTER_CONFIG` JSON, then to defaults. Missing fields take their defaults.
+    pub fn from_env() -> Result<FilterConfig, FilterError> {
+        // ---
+        FilterConfig::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// Same as `from_env`, reading variables through `lookup` so callers (and tests)
+    /// need not touch the process environment
+    pub fn from_env_with<F>(lookup: F) -> Result<FilterConfig, FilterError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        // ---
+        if let Some(path) = lookup(CONFIG_FILE_ENV) {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| FilterError::InvalidConfig(format!("{path}: {err}")))?;
+            return FilterConfig::from_json(&text);
+        }
+        if let Some(text) = lookup(CONFIG_JSON_ENV) {
+            return FilterConfig::from_json(&text);
+        }
+        Ok(FilterConfig::default())
+    }
+
+    /// Parses a config from JSON text, rejecting contradictory settings (see `validate`)
+    pub fn from_json(text: &str) -> Result<FilterConfig, FilterError> {
+        // ---
+        let config: FilterConfig = serde_json::from_str(text)
+            .map_err(|err| FilterError::InvalidConfig(err.to_string()))?;
+        config.assumed_offset()?;
+        config.deny_regex()?;
+        config.entity_id_regex()?;
+        config.check_consistency()?;
+        Ok(config)
+    }
+
+    /// Checks that the settings do not contradict each other, returning every conflict
+    /// found. Two kinds are detected:
+    /// - An id singled out by `priority_overrides` that the id filters always drop: it
+    ///   matches `deny_pattern` or misses `entity_id_pattern` (an allow/deny clash)
+    /// - `min_expected_survivors` above what the output caps can ever return
+    ///   (`top_urgent + top_normal`, or 0 under `per_entity_limit: 0` or a zero `sample`)
+    ///
+    /// Runs when a config is loaded (`from_json`, hence `from_env` and `cached_config`)
+    /// and on every `try_process_actions` call, so configs built in code are checked too.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        // ---
+        let mut problems = Vec::new();
+        let mut override_ids: Vec<&str> =
+            self.priority_overrides.keys().map(String::as_str).collect();
+        override_ids.sort_unstable();
+        if let Ok(Some(deny)) = self.deny_regex() {
+            for id in override_ids.iter().filter(|id| deny.is_match(id)) {
+                problems.push(format!("priority_overrides entry '{id}' matches deny_pattern"));
+            }
+        }
+        if let Ok(Some(format)) = self.entity_id_regex() {
+            for id in override_ids.iter().filter(|id| !format.is_match(id)) {
+                problems.push(format!(
+                    "priority_overrides entry '{id}' does not match entity_id_pattern"
+                ));
+            }
+        }
+
+        if let Some(minimum) = self.min_expected_survivors {
+            let caps = [
+                self.top_urgent.zip(self.top_normal).map(|(urgent, normal)| {
+                    ("top_urgent + top_normal", urgent.saturating_add(normal))
+                }),
+                (self.per_entity_limit == Some(0)).then_some(("per_entity_limit", 0)),
+                self.sample.filter(|sample| sample.fraction <= 0.0).map(|_| ("sample.fraction", 0)),
+            ];
+            for (cap, most) in caps.into_iter().flatten() {
+                if minimum > most {
+                    problems.push(format!(
+                        "min_expected_survivors {minimum} exceeds the {most} that {cap} allows"
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// `validate` as a `FilterError::InvalidConfig` naming every conflict
+    pub(crate) fn check_consistency(&self) -> Result<(), FilterError> {
+        // ---
+        self.validate().map_err(|problems| FilterError::InvalidConfig(problems.join("; ")))
+    }
+
+    /// Effective `gzip_min_bytes`
+    pub fn gzip_min_bytes(&self) -> usize {
+        // ---
+        self.gzip_min_bytes.unwrap_or(DEFAULT_GZIP_MIN_BYTES)
+    }
+
+    /// Parsed `assumed_utc_offset`, defaulting to UTC
+    pub fn assumed_offset(&self) -> Result<FixedOffset, FilterError> {
+        // ---
+        match &self.assumed_utc_offset {
+            None => Ok(timestamp::utc()),
+            Some(text) => text.parse().map_err(|_| {
+                FilterError::InvalidConfig(format!("assumed_utc_offset '{text}' is not +HH:MM"))
+            }),
+        }
+    }
+
+    /// Compiled `deny_pattern`, if any
+    pub fn deny_regex(&self) -> Result<Option<Regex>, FilterError> {
+        // ---
+        self.deny_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| FilterError::InvalidConfig(format!("deny_pattern: {err}")))
+    }
+
+    /// Compiled `entity_id_pattern`, if any
+    pub fn entity_id_regex(&self) -> Result<Option<Regex>, FilterError> {
+        // ---
+        self.entity_id_pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| FilterError::InvalidConfig(format!("entity_id_pattern: {err}")))
+    }
+}
+
+/// Holds a parsed config so warm invocations skip re-loading it.
+/// Unlike `OnceLock`, the slot can be cleared (see `reset`) so tests can reload.
+pub struct ConfigCache {
+    slot: RwLock<Option<Arc<FilterConfig>>>,
+}
+
+impl ConfigCache {
+    // ---
+    pub const fn new() -> Self {
+        ConfigCache { slot: RwLock::new(None) }
+    }
+
+    /// Returns the cached config, running `load` only if nothing is cached yet.
+    /// Load failures are not cached, so the next call retries.
+    pub fn get_or_load<F>(&self, load: F) -> Result<Arc<FilterConfig>, FilterError>
+    where
+        F: FnOnce() -> Result<FilterConfig, FilterError>,
+    {
+        // ---
+        if let Some(config) = self.slot.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            return Ok(Arc::clone(config));
+        }
+
+        let mut slot = self.slot.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(config) = slot.as_ref() {
+            return Ok(Arc::clone(config)); // Another caller loaded it first
+        }
+        let config = Arc::new(load()?);
+        *slot = Some(Arc::clone(&config));
+        Ok(config)
+    }
+
+    /// Drops the cached config so the next access reloads it
+    pub fn reset(&self) {
+        // ---
+        *self.slot.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+impl Default for ConfigCache {
+    // ---
+    fn default() -> Self {
+        ConfigCache::new()
+    }
+}
+
+static CONFIG_CACHE: ConfigCache = ConfigCache::new();
+
+/// Process-wide config, loaded from the environment on first use
+pub fn cached_config() -> Result<Arc<FilterConfig>, FilterError> {
+    // ---
+    CONFIG_CACHE.get_or_load(FilterConfig::from_env)
+}
+
+/// Clears the process-wide config cache (intended for tests)
+pub fn reset_config_cache() {
+    // ---
+    CONFIG_CACHE.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{ensure, Result};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_default_windows_use_exported_constants() -> Result<()> {
+        // ---
+        ensure!(DEFAULT_FUTURE_WINDOW_DAYS == 90 && DEFAULT_PAST_WINDOW_DAYS == 7);
+
+        let config = FilterConfig::default();
+        let now = DateTime::parse_from_rfc3339("2025-06-28T00:00:00Z")?.with_timezone(&Utc);
+        let action = |past: i64, future: i64| crate::domain::Action {
+            last_action_time: now - chrono::Duration::days(past),
+            next_action_time: now + chrono::Duration::days(future),
+            ..Default::default()
+        };
+        let passes = |action| crate::filter::passes_time_filters(&action, now, &config);
+        ensure!(passes(action(DEFAULT_PAST_WINDOW_DAYS + 1, DEFAULT_FUTURE_WINDOW_DAYS)));
+        ensure!(!passes(action(DEFAULT_PAST_WINDOW_DAYS, 1)));
+        ensure!(!passes(action(DEFAULT_PAST_WINDOW_DAYS + 1, DEFAULT_FUTURE_WINDOW_DAYS + 1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_every_conflict() -> Result<()> {
+        // ---
+        ensure!(FilterConfig::default().validate().is_ok(), "Expected defaults to be consistent");
+
+        let config = FilterConfig {
+            deny_pattern: Some("^test_".to_string()),
+            priority_overrides: HashMap::from([
+                ("test_1".to_string(), Priority::Urgent),
+                ("entity_1".to_string(), Priority::Urgent),
+            ]),
+            min_expected_survivors: Some(5),
+            top_urgent: Some(1),
+            top_normal: Some(2),
+            ..Default::default()
+        };
+        let problems = config.validate().err().unwrap_or_default();
+        ensure!(
+            problems
+                == [
+                    "priority_overrides entry 'test_1' matches deny_pattern",
+                    "min_expected_survivors 5 exceeds the 3 that top_urgent + top_normal allows",
+                ],
+            "Unexpected problems {problems:?}"
+        );
+
+        let err =
+            FilterConfig::from_json(r#"{"per_entity_limit": 0, "min_expected_survivors": 5}"#)
+                .err();
+        ensure!(
+            err.is_some_and(|e| e.to_string().contains("per_entity_limit")),
+            "Expected from_json to reject contradictory configs"
+        );
+
+        let built_in_code = FilterConfig { min_expected_survivors: Some(5), ..config };
+        ensure!(
+            crate::filter::try_process_actions(Vec::new(), DateTime::UNIX_EPOCH, &built_in_code)
+                .is_err(),
+            "Expected try_process_actions to reject contradictory configs"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_cache_loads_once() -> Result<()> {
+        // ---
+        let cache = ConfigCache::new();
+        let loads = AtomicUsize::new(0);
+        let loader = || {
+            loads.fetch_add(1, Ordering::SeqCst);
+            FilterConfig::from_json(r#"{"dedup_strategy": "most_complete"}"#)
+        };
+
+        for _ in 0..3 {
+            let config = cache.get_or_load(loader)?;
+            ensure!(config.dedup_strategy == DedupStrategy::MostComplete, "Unexpected config");
+        }
+        ensure!(loads.load(Ordering::SeqCst) == 1, "Expected a single load across calls");
+
+        cache.reset();
+        cache.get_or_load(loader)?;
+        ensure!(loads.load(Ordering::SeqCst) == 2, "Expected reset to force a reload");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_granularity_truncation() -> Result<()> {
+        // ---
+        let a = DateTime::parse_from_rfc3339("2025-07-01T10:15:03.250Z")?.with_timezone(&Utc);
+        let b = DateTime::parse_from_rfc3339("2025-07-01T10:15:59Z")?.with_timezone(&Utc);
+
+        ensure!(
+            Granularity::Minute.truncate(a) == Granularity::Minute.truncate(b),
+            "Expected timestamps within the same minute to compare equal"
+        );
+        ensure!(
+            Granularity::Second.truncate(a) != Granularity::Second.truncate(b),
+            "Expected timestamps to differ at second granularity"
+        );
+        ensure!(
+            Granularity::Day.truncate(a).date_naive() == a.date_naive()
+                && Granularity::Day.truncate(a).timestamp() % 86_400 == 0,
+            "Expected day granularity to truncate to midnight"
+        );
+
+        Ok(())
+    }
+}
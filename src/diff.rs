@@ -0,0 +1,51 @@
+use crate::domain::Action;
+use std::collections::HashSet;
+
+/// Changes between a previously-returned result set and a new one
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResultDiff {
+    /// Actions whose entity_id was not in the previous set, in result order
+    pub added: Vec<Action>,
+    /// Previous entity_ids missing from the new result, in their previous order
+    pub removed: Vec<String>,
+    /// Actions whose entity_id was already in the previous set, in result order
+    pub still_present: Vec<Action>,
+}
+
+/// Compares a new result set against the entity_ids a client already holds
+pub fn diff_results(previous_ids: &[String], current: Vec<Action>) -> ResultDiff {
+    // ---
+    let previous: HashSet<&str> = previous_ids.iter().map(String::as_str).collect();
+    let current_ids: HashSet<String> = current.iter().map(|a| a.entity_id.clone()).collect();
+
+    let (still_present, added) =
+        current.into_iter().partition(|a| previous.contains(a.entity_id.as_str()));
+    let removed =
+        previous_ids.iter().filter(|id| !current_ids.contains(id.as_str())).cloned().collect();
+
+    ResultDiff { added, removed, still_present }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{ensure, Result};
+
+    #[test]
+    fn test_diff_partitions_added_and_removed() -> Result<()> {
+        // ---
+        let action = |id: &str| Action { entity_id: id.to_string(), ..Default::default() };
+        let previous = ["entity_1", "entity_2"].map(String::from);
+
+        let diff = diff_results(&previous, vec![action("entity_3"), action("entity_1")]);
+
+        let ids = |actions: &[Action]| -> Vec<String> {
+            actions.iter().map(|a| a.entity_id.clone()).collect()
+        };
+        ensure!(ids(&diff.added) == ["entity_3"], "Unexpected added {:?}", diff.added);
+        ensure!(diff.removed == ["entity_2"], "Unexpected removed {:?}", diff.removed);
+        ensure!(ids(&diff.still_present) == ["entity_1"], "Unexpected {:?}", diff.still_present);
+
+        Ok(())
+    }
+}
@@ -1,26 +1,213 @@
-use chrono::{DateTime, Utc};
+use crate::timestamp;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::{self, Deserializer, Unexpected, Visitor};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::fmt;
 
-/// Priority level for actions, with Urgent taking precedence over Normal
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+/// Priority level for actions, with Urgent taking precedence over Normal.
+/// Deserializes from its lowercase name or its integer rank (0 = Urgent, 1 = Normal);
+/// an explicit `null` yields the default (Normal), the same as a missing field.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "lowercase")]
 pub enum Priority {
     Urgent,
+    #[default]
     Normal,
 }
 
+impl Priority {
+    /// Serialized names, in rank order
+    pub const NAMES: &'static [&'static str] = &["urgent", "normal"];
+
+    /// Looks up a priority by its serialized name, ignoring case and surrounding whitespace
+    pub fn from_name(name: &str) -> Option<Priority> {
+        // ---
+        match name.trim().to_ascii_lowercase().as_str() {
+            "urgent" => Some(Priority::Urgent),
+            "normal" => Some(Priority::Normal),
+            _ => None,
+        }
+    }
+
+    /// Looks up a priority by its integer rank
+    pub fn from_rank(rank: u64) -> Option<Priority> {
+        // ---
+        match rank {
+            0 => Some(Priority::Urgent),
+            1 => Some(Priority::Normal),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Priority {
+    // ---
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PriorityVisitor)
+    }
+}
+
+struct PriorityVisitor;
+
+impl Visitor<'_> for PriorityVisitor {
+    // ---
+    type Value = Priority;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "one of {:?} or an integer rank 0 (urgent) or 1 (normal)", Priority::NAMES)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Priority, E> {
+        // ---
+        Priority::from_name(value).ok_or_else(|| E::unknown_variant(value, Priority::NAMES))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Priority, E> {
+        // ---
+        Priority::from_rank(value)
+            .ok_or_else(|| E::invalid_value(Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Priority, E> {
+        // ---
+        Ok(Priority::default())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Priority, E> {
+        // ---
+        u64::try_from(value)
+            .ok()
+            .and_then(Priority::from_rank)
+            .ok_or_else(|| E::invalid_value(Unexpected::Signed(value), &self))
+    }
+}
+
+/// Calendar-view bucket for an action's next_action_time relative to now
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DueBucket {
+    /// Already due (next_action_time before now)
+    Overdue,
+    /// Due within the next 7 days
+    ThisWeek,
+    /// Due within the next 30 days
+    ThisMonth,
+    /// Due more than 30 days out
+    Later,
+}
+
+impl DueBucket {
+    /// Bucket for `next_action_time` as seen at `now`
+    pub fn of(next_action_time: DateTime<Utc>, now: DateTime<Utc>) -> DueBucket {
+        // ---
+        let ahead = next_action_time - now;
+        if ahead < Duration::zero() {
+            DueBucket::Overdue
+        } else if ahead <= Duration::days(7) {
+            DueBucket::ThisWeek
+        } else if ahead <= Duration::days(30) {
+            DueBucket::ThisMonth
+        } else {
+            DueBucket::Later
+        }
+    }
+}
+
 /// Represents an action to be performed on an entity
-#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct Action {
     /// Unique identifier for the entity this action applies to
     pub entity_id: String,
     /// Timestamp of when this action was last performed
+    #[serde(deserialize_with = "timestamp::deserialize")]
     pub last_action_time: DateTime<Utc>,
     /// Timestamp of when this action should be performed next
+    #[serde(deserialize_with = "timestamp::deserialize")]
     pub next_action_time: DateTime<Utc>,
-    /// Priority level of this action
+    /// Priority level of this action (Normal when missing or null)
+    #[serde(default)]
     pub priority: Priority,
+    /// Optional free-form notes supplied by the producer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Optional labels attached by the producer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Optional timestamp of when the producer created this record
+    #[serde(
+        default,
+        deserialize_with = "timestamp::deserialize_opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Opaque caller-supplied payload, returned untouched and ignored by deduplication
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    /// Optional producer-assigned relevance score (see `min_score`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+impl PartialEq for Action {
+    /// Field-wise equality; scores compare by bit pattern so a NaN score (which callers
+    /// can build, though JSON cannot carry one) still equals itself
+    fn eq(&self, other: &Self) -> bool {
+        // ---
+        self.entity_id == other.entity_id
+            && self.last_action_time == other.last_action_time
+            && self.next_action_time == other.next_action_time
+            && self.priority == other.priority
+            && self.notes == other.notes
+            && self.tags == other.tags
+            && self.created_at == other.created_at
+            && self.metadata == other.metadata
+            && self.score.map(f64::to_bits) == other.score.map(f64::to_bits)
+    }
+}
+
+impl Eq for Action {}
+
+impl Action {
+    /// Number of optional fields carrying data (used to rank duplicate records)
+    pub fn completeness(&self) -> usize {
+        // ---
+        let notes = self.notes.as_deref().is_some_and(|n| !n.is_empty());
+        let tags = !self.tags.is_empty();
+        let created_at = self.created_at.is_some();
+        let score = self.score.is_some();
+
+        [notes, tags, created_at, score].into_iter().filter(|present| *present).count()
+    }
+
+    /// Canonical form for hashing and content comparison: entity_id trimmed and lowercased,
+    /// tags sorted. Timestamps are already UTC, so two records describing the same action
+    /// canonicalize to equal values.
+    pub fn canonical(&self) -> Action {
+        // ---
+        let mut tags = self.tags.clone();
+        tags.sort_unstable();
+        Action { entity_id: self.entity_id.trim().to_lowercase(), tags, ..self.clone() }
+    }
+
+    /// Rough memory footprint: the struct itself plus its string and tag allocations.
+    /// `metadata` is approximated by its serialized length.
+    pub fn estimated_bytes(&self) -> usize {
+        // ---
+        let strings = self.entity_id.capacity()
+            + self.notes.as_ref().map_or(0, String::capacity)
+            + self.tags.capacity() * std::mem::size_of::<String>()
+            + self.tags.iter().map(String::capacity).sum::<usize>();
+        let metadata = self.metadata.as_ref().map_or(0, |value| value.to_string().len());
+
+        std::mem::size_of::<Action>() + strings + metadata
+    }
+}
+
+/// Estimated memory used by a batch of actions, for sizing batch limits
+pub fn estimate_batch_bytes(actions: &[Action]) -> usize {
+    // ---
+    actions.iter().map(Action::estimated_bytes).sum()
 }
 
 impl Ord for Action {
@@ -37,3 +224,118 @@ impl PartialOrd for Action {
         Some(self.cmp(other))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{ensure, Result};
+
+    #[test]
+    fn test_nan_score_action_equals_itself() -> Result<()> {
+        // ---
+        let action = Action { score: Some(f64::NAN), ..Default::default() };
+        ensure!(action == action.clone(), "Expected a NaN-scored action to equal its clone");
+        ensure!(
+            action != Action { score: Some(1.0), ..Default::default() },
+            "Expected different scores to differ"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_accepts_name_or_rank() -> Result<()> {
+        // ---
+        let from_rank: Priority = serde_json::from_str("0")?;
+        let from_name: Priority = serde_json::from_str(r#""urgent""#)?;
+        ensure!(from_rank == Priority::Urgent, "Expected 0 to be Urgent, got {from_rank:?}");
+        ensure!(from_name == Priority::Urgent, "Expected 'urgent' to be Urgent");
+        ensure!(serde_json::from_str::<Priority>("1")? == Priority::Normal, "Expected 1 = Normal");
+
+        ensure!(
+            serde_json::from_str::<Priority>("null")? == Priority::Normal,
+            "Expected null = Normal"
+        );
+
+        let err = serde_json::from_str::<Priority>("7").unwrap_err().to_string();
+        ensure!(err.contains("urgent") && err.contains("normal"), "Unhelpful error: {err}");
+
+        let err = serde_json::from_str::<Priority>(r#""low""#).unwrap_err().to_string();
+        ensure!(err.contains("unknown variant"), "Unhelpful error: {err}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_batch_bytes() -> Result<()> {
+        // ---
+        let action = Action {
+            entity_id: "entity_1".to_string(),
+            notes: Some("call back".to_string()),
+            tags: vec!["vip".to_string(), "billing".to_string()],
+            ..Default::default()
+        };
+        let single = estimate_batch_bytes(std::slice::from_ref(&action));
+        let strings = "entity_1".len() + "call back".len() + "vip".len() + "billing".len();
+
+        ensure!(
+            single >= std::mem::size_of::<Action>() + strings,
+            "Expected at least the struct and its strings, got {single}"
+        );
+        ensure!(single < 1024, "Expected a small action to stay small, got {single}");
+        ensure!(
+            estimate_batch_bytes(&[action.clone(), action]) == 2 * single,
+            "Expected the estimate to scale with the batch"
+        );
+        ensure!(estimate_batch_bytes(&[]) == 0, "Expected an empty batch to cost nothing");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonical_ignores_tag_order_and_id_case() -> Result<()> {
+        // ---
+        let action: Action = serde_json::from_str(
+            r#"{"entity_id": "Entity_1", "last_action_time": "2025-06-01T02:00:00+02:00",
+                "next_action_time": "2025-07-01T00:00:00Z", "tags": ["vip", "billing"]}"#,
+        )?;
+        let other: Action = serde_json::from_str(
+            r#"{"entity_id": " entity_1", "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "2025-07-01T00:00:00Z", "tags": ["billing", "vip"]}"#,
+        )?;
+
+        ensure!(action != other, "Expected the raw records to differ");
+        ensure!(action.canonical() == other.canonical(), "Expected equal canonical forms");
+        ensure!(action.canonical().entity_id == "entity_1", "Expected a lowercased entity_id");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_padded_priority_name() -> Result<()> {
+        // ---
+        let padded: Priority = serde_json::from_str(r#"" Urgent ""#)?;
+        ensure!(padded == Priority::Urgent, "Expected ' Urgent ' to be Urgent, got {padded:?}");
+        ensure!(
+            serde_json::from_str::<Priority>("\"\\tNORMAL\\n\"")? == Priority::Normal,
+            "Expected tabs, newlines and case to be ignored"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_priority_matches_missing_priority() -> Result<()> {
+        // ---
+        let times = r#""last_action_time": "2025-06-01T00:00:00Z",
+            "next_action_time": "2025-07-01T00:00:00Z""#;
+        let with_null: Action =
+            serde_json::from_str(&format!(r#"{{"entity_id": "e", {times}, "priority": null}}"#))?;
+        let missing: Action = serde_json::from_str(&format!(r#"{{"entity_id": "e", {times}}}"#))?;
+
+        ensure!(with_null.priority == Priority::default(), "Expected the default priority");
+        ensure!(with_null == missing, "Expected null and missing priority to match");
+
+        Ok(())
+    }
+}
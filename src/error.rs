@@ -0,0 +1,165 @@
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Recoverable errors surfaced to callers of the Lambda
+#[derive(Debug)]
+pub enum FilterError {
+    /// The payload could not be deserialized into actions
+    InvalidPayload(serde_json::Error),
+    /// The filter configuration could not be loaded
+    InvalidConfig(String),
+    /// The request named an `action` this function does not support
+    UnsupportedAction(String),
+    /// The batch held no actions (`EmptyInputPolicy::Error`)
+    EmptyInput,
+    /// Actions at these input positions have an empty entity_id
+    EmptyEntityId(Vec<usize>),
+    /// Actions at these input positions have an entity_id not matching `entity_id_pattern`
+    MalformedEntityId(Vec<usize>),
+    /// Records at these input positions could not be deserialized (`ValidationMode::CollectAll`).
+    /// Reported under the same problem type as `InvalidPayload`.
+    InvalidRecords(Vec<(usize, String)>),
+    /// Fewer actions survived than `min_expected_survivors`, suggesting a misconfiguration
+    TooFewSurvivors { kept: usize, expected: usize },
+    /// The payload is larger than `max_payload_bytes`
+    PayloadTooLarge { size: usize, limit: usize },
+    /// The response could not be gzipped
+    Compression(std::io::Error),
+}
+
+impl FilterError {
+    /// Short machine-readable identifier for this error
+    pub fn code(&self) -> &'static str {
+        // ---
+        match self {
+            FilterError::InvalidPayload(_) | FilterError::InvalidRecords(_) => "invalid_payload",
+            FilterError::InvalidConfig(_) => "invalid_config",
+            FilterError::UnsupportedAction(_) => "unsupported_action",
+            FilterError::EmptyInput => "empty_input",
+            FilterError::EmptyEntityId(_) => "empty_entity_id",
+            FilterError::MalformedEntityId(_) => "malformed_entity_id",
+            FilterError::TooFewSurvivors { .. } => "too_few_survivors",
+            FilterError::PayloadTooLarge { .. } => "payload_too_large",
+            FilterError::Compression(_) => "compression_failed",
+        }
+    }
+
+    /// HTTP-style status code describing this error
+    pub fn status(&self) -> u16 {
+        // ---
+        match self {
+            FilterError::InvalidPayload(_) | FilterError::InvalidRecords(_) => 400,
+            FilterError::InvalidConfig(_) => 500,
+            FilterError::UnsupportedAction(_) => 400,
+            FilterError::EmptyInput => 422,
+            FilterError::EmptyEntityId(_) => 422,
+            FilterError::MalformedEntityId(_) => 422,
+            FilterError::TooFewSurvivors { .. } => 422,
+            FilterError::PayloadTooLarge { .. } => 413,
+            FilterError::Compression(_) => 500,
+        }
+    }
+
+    /// Human-readable summary that does not vary between occurrences
+    pub fn title(&self) -> &'static str {
+        // ---
+        match self {
+            FilterError::InvalidPayload(_) | FilterError::InvalidRecords(_) => "Invalid payload",
+            FilterError::InvalidConfig(_) => "Invalid configuration",
+            FilterError::UnsupportedAction(_) => "Unsupported action",
+            FilterError::EmptyInput => "Empty input",
+            FilterError::EmptyEntityId(_) => "Empty entity_id",
+            FilterError::MalformedEntityId(_) => "Malformed entity_id",
+            FilterError::TooFewSurvivors { .. } => "Too few survivors",
+            FilterError::PayloadTooLarge { .. } => "Payload too large",
+            FilterError::Compression(_) => "Compression failed",
+        }
+    }
+
+    /// Renders this error as an RFC 7807 problem+json object
+    pub fn to_problem_json(&self) -> Value {
+        // ---
+        let mut problem = json!({
+            "type": format!("urn:action-filter:{}", self.code()),
+            "title": self.title(),
+            "status": self.status(),
+            "detail": self.to_string(),
+        });
+        if let (Value::Object(problem), Value::Object(extra)) = (&mut problem, self.extensions()) {
+            problem.extend(extra);
+        }
+        problem
+    }
+
+    /// Variant-specific RFC 7807 extension members
+    fn extensions(&self) -> Value {
+        // ---
+        match self {
+            FilterError::EmptyEntityId(indices) | FilterError::MalformedEntityId(indices) => {
+                json!({ "indices": indices })
+            }
+            FilterError::TooFewSurvivors { kept, expected } => {
+                json!({ "kept": kept, "expected": expected })
+            }
+            FilterError::PayloadTooLarge { size, limit } => json!({ "size": size, "limit": limit }),
+            FilterError::InvalidRecords(errors) => {
+                let errors: Vec<Value> = errors
+                    .iter()
+                    .map(|(index, detail)| json!({ "index": index, "detail": detail }))
+                    .collect();
+                json!({ "errors": errors })
+            }
+            _ => Value::Null,
+        }
+    }
+}
+
+impl fmt::Display for FilterError {
+    // ---
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::InvalidPayload(err) => write!(f, "Invalid payload: {err}"),
+            FilterError::InvalidConfig(msg) => write!(f, "Invalid configuration: {msg}"),
+            FilterError::UnsupportedAction(name) => {
+                write!(f, "Unsupported action '{name}', expected one of: describe_config")
+            }
+            FilterError::EmptyInput => write!(f, "The request contained no actions"),
+            FilterError::EmptyEntityId(indices) => {
+                write!(f, "Actions at input indices {indices:?} have an empty entity_id")
+            }
+            FilterError::MalformedEntityId(indices) => {
+                write!(f, "Actions at input indices {indices:?} have a malformed entity_id")
+            }
+            FilterError::TooFewSurvivors { kept, expected } => {
+                write!(f, "Only {kept} actions survived filtering, expected at least {expected}")
+            }
+            FilterError::PayloadTooLarge { size, limit } => {
+                write!(f, "Payload is {size} bytes, over the {limit} byte limit")
+            }
+            FilterError::Compression(err) => write!(f, "Could not gzip the response: {err}"),
+            FilterError::InvalidRecords(errors) => {
+                let details: Vec<String> =
+                    errors.iter().map(|(index, detail)| format!("[{index}] {detail}")).collect();
+                write!(f, "Invalid payload: {}", details.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for FilterError {
+    // ---
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FilterError::InvalidPayload(err) => Some(err),
+            FilterError::Compression(err) => Some(err),
+            FilterError::InvalidConfig(_)
+            | FilterError::UnsupportedAction(_)
+            | FilterError::EmptyInput
+            | FilterError::EmptyEntityId(_)
+            | FilterError::MalformedEntityId(_)
+            | FilterError::InvalidRecords(_)
+            | FilterError::TooFewSurvivors { .. }
+            | FilterError::PayloadTooLarge { .. } => None,
+        }
+    }
+}
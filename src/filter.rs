@@ -0,0 +1,3235 @@
+use crate::business_days::add_business_days;
+use crate::config::{
+    AdaptivePriority, AtNowPolicy, DedupKey, DedupStrategy, EmptyIdPolicy, EmptyInputPolicy,
+    FilterConfig, Granularity, LengthPolicy, MalformedIdPolicy, MissingScorePolicy,
+    PriorityTieBreak, SampleConfig, SkewPolicy, SortKey, TieBreak, ValidationAction,
+    ValidationMode, ZeroDurationPolicy, DEFAULT_FUTURE_WINDOW_DAYS, DEFAULT_PAST_WINDOW_DAYS,
+};
+use crate::domain::{Action, DueBucket, Priority};
+use crate::error::FilterError;
+use crate::hashing::StableHasher;
+use crate::metrics::METRICS;
+use crate::report::{DedupAuditEntry, FilterReport, OccurrenceDecision, PriorityOverride};
+use crate::timestamp::saturating_add;
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde_json::Value;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::time::{Duration as StdDuration, Instant};
+
+/// How many records the filter loop examines between deadline checks
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
+/// How many conflicting pairs the per-run priority-conflict warning lists as examples
+const CONFLICT_EXAMPLES: usize = 3;
+
+/// An action paired with its position in the caller's input
+type Indexed = (usize, Action);
+
+/// Why an action failed the per-record filters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rejection {
+    Validation,
+    Denied,
+    LowScore,
+    Future,
+    Stale,
+}
+
+impl Rejection {
+    /// Drop reason as named in `FilterReport`
+    fn name(self) -> &'static str {
+        // ---
+        match self {
+            Rejection::Validation => "validation",
+            Rejection::Denied => "denied",
+            Rejection::LowScore => "low_score",
+            Rejection::Future => "future",
+            Rejection::Stale => "stale",
+        }
+    }
+}
+
+/// Window bounds for one invocation, already truncated to the comparison granularity
+struct Windows {
+    unit: Granularity,
+    deny: Option<Regex>, // Compiled deny_pattern; invalid patterns are rejected at load
+    format: Option<Regex>, // Compiled entity_id_pattern, likewise
+    next_limit: DateTime<Utc>, // Latest allowed next_action_time
+    last_limit: DateTime<Utc>, // last_action_time must be strictly before this
+}
+
+impl Windows {
+    // ---
+    fn new(now: DateTime<Utc>, config: &FilterConfig) -> Self {
+        // ---
+        Windows {
+            deny: config.deny_regex().ok().flatten(),
+            format: config.entity_id_regex().ok().flatten(),
+            ..Windows::time_only(now, config)
+        }
+    }
+
+    /// Window bounds alone, without the compiled deny_pattern and entity_id_pattern
+    fn time_only(now: DateTime<Utc>, config: &FilterConfig) -> Self {
+        // ---
+        let unit = config.comparison_granularity;
+        let (future, past) = (DEFAULT_FUTURE_WINDOW_DAYS, DEFAULT_PAST_WINDOW_DAYS);
+        let (next_limit, last_limit) = if config.use_business_days {
+            let holidays = config.holidays.as_ref();
+            (add_business_days(now, future, holidays), add_business_days(now, -past, holidays))
+        } else {
+            (
+                saturating_add(now, Duration::days(future)),
+                saturating_add(now, -Duration::days(past)),
+            )
+        };
+        // Both limits move later: `next` may run that much past the future boundary and
+        // `last` may fall that much short of the staleness boundary
+        let epsilon = Duration::try_seconds(config.epsilon_seconds.max(0)).unwrap_or(Duration::MAX);
+        Windows {
+            unit,
+            deny: None,
+            format: None,
+            next_limit: saturating_add(unit.truncate(next_limit), epsilon),
+            last_limit: saturating_add(unit.truncate(last_limit), epsilon),
+        }
+    }
+
+    /// Checks a single action against the validation and window rules
+    fn rejection(&self, action: &Action, config: &FilterConfig) -> Option<Rejection> {
+        // ---
+        let enforce = config.validation_action == ValidationAction::Enforce;
+        if enforce && action.entity_id.trim().is_empty() {
+            return Some(Rejection::Validation);
+        }
+        if enforce && self.format.as_ref().is_some_and(|format| !format.is_match(&action.entity_id))
+        {
+            return Some(Rejection::Validation);
+        }
+        if self.deny.as_ref().is_some_and(|deny| deny.is_match(&action.entity_id)) {
+            return Some(Rejection::Denied);
+        }
+        if enforce && is_dropped_zero_duration(action, config) {
+            return Some(Rejection::Validation);
+        }
+        if below_min_score(action, config) {
+            return Some(Rejection::LowScore);
+        }
+        self.time_rejection(action)
+    }
+
+    /// Checks a single action against the next/last action time windows only
+    fn time_rejection(&self, action: &Action) -> Option<Rejection> {
+        // ---
+        if self.unit.truncate(action.next_action_time) > self.next_limit {
+            return Some(Rejection::Future);
+        }
+        if self.unit.truncate(action.last_action_time) >= self.last_limit {
+            return Some(Rejection::Stale);
+        }
+        None
+    }
+}
+
+/// True when `action` satisfies the `DEFAULT_FUTURE_WINDOW_DAYS` next_action_time and
+/// `DEFAULT_PAST_WINDOW_DAYS` last_action_time windows (at `config.comparison_granularity`).
+/// Validation, skew and dedup are not applied.
+pub fn passes_time_filters(action: &Action, now: DateTime<Utc>, config: &FilterConfig) -> bool {
+    // ---
+    Windows::time_only(now, config).time_rejection(action).is_none()
+}
+
+/// Filters and sorts actions according to business rules:
+/// - Drops actions with an empty entity_id (see `try_process_actions` for the Error policy)
+/// - Drops actions whose entity_id does not match `config.entity_id_pattern`
+/// - Drops actions whose entity_id matches `config.deny_pattern`
+/// - Rejects or truncates entity_ids longer than `config.max_entity_id_len`
+/// - Rejects or clamps last_action_time beyond `config.max_future_skew_minutes` ahead of now
+/// - Drops zero-duration actions (last == next) when `config.zero_duration_policy` is Drop
+/// - Drops actions scored below `config.min_score` (unscored ones per `missing_score_policy`)
+/// - Filters out actions with next_action_time more than `DEFAULT_FUTURE_WINDOW_DAYS` (90)
+///   days from now
+/// - Filters out actions with last_action_time less than `DEFAULT_PAST_WINDOW_DAYS` (7)
+///   days ago (both compared after truncating to
+///   `config.comparison_granularity`, days by default; counted in weekdays when
+///   `config.use_business_days` is set)
+/// - Applies `config.priority_overrides` per entity_id (changes listed in the report)
+/// - Raises priorities ranked below `config.collapse_below` to that tier, when set
+/// - Deduplicates by `config.dedup_by` (entity_id by default) per `config.dedup_strategy`
+///   (last occurrence by default), keeping up to `config.per_entity_limit` per key when set
+/// - Sorts by `config.sort_key` (priority by default: Urgent first, then Normal)
+/// - Keeps a seeded `config.sample` fraction of survivors, when configured
+/// - Keeps at most `config.top_urgent` / `config.top_normal` actions of each priority
+/// - Drops the lowest priority tiers while more than `config.adaptive_priority` remain
+pub fn process_actions(
+    input: Vec<Action>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Vec<Action> {
+    // ---
+    process_actions_with_report(input, now, config).0
+}
+
+/// Checks that every output action is an unmodified copy of some input action.
+/// Returns a message naming the first output position with no matching input.
+///
+/// Only meaningful when the pipeline passes records through untouched: the merging dedup
+/// strategies, `SkewPolicy::Clamp`, `LengthPolicy::Truncate`, `collapse_below` and
+/// `priority_overrides` legitimately rewrite records.
+pub fn assert_output_subset(input: &[Action], output: &[Action]) -> Result<(), String> {
+    // ---
+    let mut by_id: HashMap<&str, Vec<&Action>> = HashMap::new();
+    for action in input {
+        by_id.entry(action.entity_id.as_str()).or_default().push(action);
+    }
+
+    for (position, action) in output.iter().enumerate() {
+        let matched = by_id
+            .get(action.entity_id.as_str())
+            .is_some_and(|candidates| candidates.contains(&action));
+        if !matched {
+            return Err(format!(
+                "output[{position}] (entity_id {:?}) matches no input action",
+                action.entity_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Same as `process_actions`, additionally returning why records were dropped.
+/// The drop-reason counts are also logged as a single summary line.
+pub fn process_actions_with_report(
+    input: Vec<Action>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> (Vec<Action>, FilterReport) {
+    // ---
+    let (output, report, _) = filter_traced(input, now, config, None);
+    (output, report)
+}
+
+/// Runs `process_actions` and reports what happened to each occurrence of `entity_id`,
+/// in input order: kept, or the stage that dropped it. Occurrences left unexamined by
+/// `deadline_ms` are not listed.
+pub fn explain_entity(
+    input: Vec<Action>,
+    entity_id: &str,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Vec<OccurrenceDecision> {
+    // ---
+    let (_, _, decisions) = filter_traced(input, now, config, Some(entity_id));
+    decisions
+}
+
+/// Body of `process_actions_with_report`, also tracing the occurrences of `explain`
+fn filter_traced(
+    input: Vec<Action>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+    explain: Option<&str>,
+) -> (Vec<Action>, FilterReport, Vec<OccurrenceDecision>) {
+    // ---
+    let started = Instant::now();
+    let deadline = config.deadline_ms.map(StdDuration::from_millis);
+    let windows = Windows::new(now, config);
+    let mut report = FilterReport { input: input.len(), ..Default::default() };
+    if let Some(threshold) = config.shared_next_time_warn_threshold {
+        report.max_shared_next_time = check_shared_next_times(&input, threshold);
+    }
+    let mut filtered: Vec<Indexed> = Vec::with_capacity(input.len());
+    let mut decisions = Vec::new();
+    let mut traced: Vec<usize> = Vec::new(); // Traced occurrences still in the running
+
+    for (index, action) in input.into_iter().enumerate() {
+        let is_traced = explain == Some(action.entity_id.as_str());
+        if index > 0
+            && index % DEADLINE_CHECK_INTERVAL == 0
+            && deadline.is_some_and(|budget| started.elapsed() > budget)
+        {
+            tracing::warn!("Deadline exceeded after {index} of {} actions", report.input);
+            report.truncated = true;
+            break;
+        }
+        let action = if config.validation_action == ValidationAction::WarnOnly {
+            for issue in validation_issues(&action, now, config) {
+                tracing::warn!(index, entity_id = %action.entity_id, issue, "Validation issue");
+            }
+            action
+        } else {
+            let Some(action) = apply_future_skew(action, now, config) else {
+                report.validation += 1;
+                note(&mut decisions, is_traced, index, Rejection::Validation.name());
+                continue;
+            };
+            let Some(action) = apply_entity_id_limit(action, config) else {
+                report.validation += 1;
+                note(&mut decisions, is_traced, index, Rejection::Validation.name());
+                continue;
+            };
+            action
+        };
+        let rejection = windows.rejection(&action, config);
+        if is_traced {
+            match rejection {
+                Some(rejection) => note(&mut decisions, true, index, rejection.name()),
+                None => traced.push(index),
+            }
+        }
+        match rejection {
+            Some(Rejection::Validation) => report.validation += 1,
+            Some(Rejection::Denied) => report.denied += 1,
+            Some(Rejection::LowScore) => report.low_score += 1,
+            Some(Rejection::Future) => report.future += 1,
+            Some(Rejection::Stale) => report.stale += 1,
+            None => {
+                let action = apply_priority_override(action, config, &mut report.override_audit);
+                filtered.push((index, collapse_priority(action, config)));
+            }
+        }
+    }
+
+    let candidates = filtered.len();
+    let (mut survivors, dedup_audit) = resolve(filtered, now, config);
+    report.duplicate = candidates - survivors.len();
+    for index in settle(&mut traced, &survivors) {
+        let kept_index = dedup_audit
+            .iter()
+            .find(|entry| entry.dropped_indices.contains(&index))
+            .map(|entry| entry.kept_index);
+        decisions.push(OccurrenceDecision { index, outcome: "duplicate", kept_index });
+    }
+    report.dedup_audit = dedup_audit;
+
+    if let Some(sample) = config.sample {
+        let resolved = survivors.len();
+        apply_sample(&mut survivors, sample);
+        report.sampled_out = resolved - survivors.len();
+        for index in settle(&mut traced, &survivors) {
+            note(&mut decisions, true, index, "sampled_out");
+        }
+    }
+
+    let sorted = survivors.len();
+    apply_band_caps(&mut survivors, config);
+    report.capped = sorted - survivors.len();
+    for index in settle(&mut traced, &survivors) {
+        note(&mut decisions, true, index, "capped");
+    }
+    for index in traced {
+        note(&mut decisions, true, index, "kept");
+    }
+    decisions.sort_by_key(|decision| decision.index);
+
+    report.kept = survivors.len();
+    report.log_summary();
+    METRICS.record(&report);
+    for (_, action) in survivors.iter().take(config.log_sample_size) {
+        tracing::info!(entity_id = %action.entity_id, priority = ?action.priority, "Sample survivor");
+    }
+
+    (survivors.into_iter().map(|(_, action)| action).collect(), report, decisions)
+}
+
+/// Records `outcome` for an occurrence of the explained entity
+fn note(
+    decisions: &mut Vec<OccurrenceDecision>,
+    traced: bool,
+    index: usize,
+    outcome: &'static str,
+) {
+    // ---
+    if traced {
+        decisions.push(OccurrenceDecision { index, outcome, kept_index: None });
+    }
+}
+
+/// Removes and returns the traced indices no longer among `survivors`
+fn settle(traced: &mut Vec<usize>, survivors: &[Indexed]) -> Vec<usize> {
+    // ---
+    let (kept, dropped) =
+        traced.iter().partition(|&&index| survivors.iter().any(|(survivor, _)| *survivor == index));
+    *traced = kept;
+    dropped
+}
+
+/// Fallible form of `process_actions_with_report` that enforces the batch-level policies
+/// which reject a whole request rather than individual records:
+/// - An invalid `deny_pattern` or `entity_id_pattern`, or settings that
+///   `FilterConfig::validate` finds contradictory, fail with `FilterError::InvalidConfig`
+/// - `EmptyInputPolicy::Error` fails an empty batch with `FilterError::EmptyInput`
+/// - `EmptyIdPolicy::Error` fails when any entity_id is empty, naming the offending indices
+///   (only the first under `ValidationMode::FailFast`; never under `ValidationAction::WarnOnly`)
+/// - `MalformedIdPolicy::Error` likewise fails when a non-empty entity_id misses
+///   `entity_id_pattern`
+/// - `min_expected_survivors` fails when fewer actions survive than expected
+pub fn try_process_actions(
+    input: Vec<Action>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Result<(Vec<Action>, FilterReport), FilterError> {
+    // ---
+    config.deny_regex()?;
+    let format = config.entity_id_regex()?;
+    config.check_consistency()?;
+    if input.is_empty() && config.empty_input_policy == EmptyInputPolicy::Error {
+        return Err(FilterError::EmptyInput);
+    }
+    let enforce = config.validation_action == ValidationAction::Enforce;
+    if config.empty_id_policy == EmptyIdPolicy::Error && enforce {
+        let empty = offending_indices(&input, config, |id| id.trim().is_empty());
+        if !empty.is_empty() {
+            return Err(FilterError::EmptyEntityId(empty));
+        }
+    }
+    if let (Some(format), MalformedIdPolicy::Error, true) =
+        (&format, config.malformed_id_policy, enforce)
+    {
+        let malformed =
+            offending_indices(&input, config, |id| !id.trim().is_empty() && !format.is_match(id));
+        if !malformed.is_empty() {
+            return Err(FilterError::MalformedEntityId(malformed));
+        }
+    }
+
+    let (actions, report) = process_actions_with_report(input, now, config);
+    if let Some(expected) = config.min_expected_survivors.filter(|min| actions.len() < *min) {
+        return Err(FilterError::TooFewSurvivors { kept: actions.len(), expected });
+    }
+    Ok((actions, report))
+}
+
+/// Input positions whose entity_id fails `check`, only the first under `ValidationMode::FailFast`
+fn offending_indices(
+    input: &[Action],
+    config: &FilterConfig,
+    check: impl Fn(&str) -> bool,
+) -> Vec<usize> {
+    // ---
+    let offending = input
+        .iter()
+        .enumerate()
+        .filter(|(_, action)| check(&action.entity_id))
+        .map(|(index, _)| index);
+    match config.validation_mode {
+        ValidationMode::FailFast => offending.take(1).collect(),
+        ValidationMode::CollectAll => offending.collect(),
+    }
+}
+
+/// Lazily applies the per-record validation and window filters, skipping dedup and sort.
+/// Nothing is allocated; survivors are yielded in input order.
+pub fn filter_only_iter<'a>(
+    input: &'a [Action],
+    now: DateTime<Utc>,
+    config: &'a FilterConfig,
+) -> impl Iterator<Item = &'a Action> + 'a {
+    // ---
+    passing(input, now, config).map(|(_, action)| action)
+}
+
+/// Runs the full pipeline but yields survivors borrowed from `input` in output order.
+/// Only the records that pass the per-record filters are copied for dedup and sort.
+///
+/// Items are the input records as they arrived, so they equal `process_actions` output
+/// only for configs that pass records through untouched. Under `priority_overrides` or
+/// `collapse_below` survivors are chosen and ordered by their rewritten priority but
+/// yielded with the original one; merging dedup strategies yield the last occurrence
+/// unmerged; `SkewPolicy::Clamp` yields skewed records unclamped; and
+/// `LengthPolicy::Truncate` deduplicates and yields the untruncated entity_ids.
+pub fn filter_actions_iter<'a>(
+    input: &'a [Action],
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> impl Iterator<Item = &'a Action> {
+    // ---
+    let candidates: Vec<Indexed> = passing(input, now, config)
+        .map(|(index, action)| {
+            let action = apply_priority_override(action.clone(), config, &mut Vec::new());
+            (index, collapse_priority(action, config))
+        })
+        .collect();
+
+    let (mut survivors, _) = resolve(candidates, now, config);
+    if let Some(sample) = config.sample {
+        apply_sample(&mut survivors, sample);
+    }
+    apply_band_caps(&mut survivors, config);
+    survivors.into_iter().map(move |(index, _)| &input[index])
+}
+
+/// Borrowed records (with their input index) that pass the per-record filters
+fn passing<'a>(
+    input: &'a [Action],
+    now: DateTime<Utc>,
+    config: &'a FilterConfig,
+) -> impl Iterator<Item = (usize, &'a Action)> + 'a {
+    // ---
+    let windows = Windows::new(now, config);
+    input.iter().enumerate().filter(move |(_, action)| {
+        // Records cannot be modified here, so over-long entity_ids under the Truncate policy
+        // pass through unchanged, and a skewed record under the Clamp policy is judged by
+        // its clamped copy (which usually fails the staleness window, but need not with an
+        // `epsilon_seconds` longer than the past window) yet yielded unclamped
+        if config.validation_action == ValidationAction::WarnOnly {
+            return windows.rejection(action, config).is_none();
+        }
+        if exceeds_entity_id_len(action, config)
+            && config.entity_id_len_policy == LengthPolicy::Reject
+        {
+            return false;
+        }
+        if exceeds_future_skew(action, now, config) {
+            return apply_future_skew((*action).clone(), now, config)
+                .is_some_and(|clamped| windows.rejection(&clamped, config).is_none());
+        }
+        windows.rejection(action, config).is_none()
+    })
+}
+
+/// Applies `process_actions`, then splits survivors into those due now
+/// (`next_action_time <= now`, or `< now` under `AtNowPolicy::Upcoming`) and those
+/// still upcoming. Both keep the sorted order.
+pub fn partition_due(
+    input: Vec<Action>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> (Vec<Action>, Vec<Action>) {
+    // ---
+    let at_now_due = config.next_at_now == AtNowPolicy::Due;
+    process_actions(input, now, config)
+        .into_iter()
+        .partition(|a| a.next_action_time < now || (at_now_due && a.next_action_time == now))
+}
+
+/// Keeps only Urgent actions that are already overdue (`next_action_time < now`),
+/// preserving their order
+pub fn urgent_overdue(actions: Vec<Action>, now: DateTime<Utc>) -> Vec<Action> {
+    // ---
+    actions
+        .into_iter()
+        .filter(|a| a.priority == Priority::Urgent && a.next_action_time < now)
+        .collect()
+}
+
+/// Rewrites each entity_id found in `mapping` to its mapped id, e.g. ahead of filtering
+/// during an entity migration, so renamed entities deduplicate under their new ids.
+/// Ids missing from `mapping` are left untouched.
+pub fn remap_entity_ids(actions: &mut [Action], mapping: &HashMap<String, String>) {
+    // ---
+    for action in actions {
+        if let Some(new_id) = mapping.get(&action.entity_id) {
+            action.entity_id.clone_from(new_id);
+        }
+    }
+}
+
+/// Cursor-style page boundary: the actions following the last one with `entity_id`, in the
+/// given (sorted) order. Unlike an offset this stays aligned when earlier records come
+/// or go between calls. Returns nothing when `entity_id` is not among `actions`.
+pub fn actions_after(actions: Vec<Action>, entity_id: &str) -> Vec<Action> {
+    // ---
+    match actions.iter().rposition(|a| a.entity_id == entity_id) {
+        Some(position) => actions.into_iter().skip(position + 1).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Groups actions by how soon they are due. Each bucket keeps the input order.
+pub fn group_by_due_bucket(
+    actions: Vec<Action>,
+    now: DateTime<Utc>,
+) -> BTreeMap<DueBucket, Vec<Action>> {
+    // ---
+    let mut buckets: BTreeMap<DueBucket, Vec<Action>> = BTreeMap::new();
+    for action in actions {
+        buckets.entry(DueBucket::of(action.next_action_time, now)).or_default().push(action);
+    }
+    buckets
+}
+
+/// Counts actions per `bucket_days`-wide slice of time until their next_action_time, as
+/// `(bucket_start_offset_days, count)` pairs in offset order. Empty buckets are omitted and
+/// overdue actions fall into negative offsets; a non-positive `bucket_days` counts as 1.
+pub fn due_histogram(
+    actions: &[Action],
+    now: DateTime<Utc>,
+    bucket_days: i64,
+) -> Vec<(i64, usize)> {
+    // ---
+    let bucket_days = bucket_days.max(1);
+    let bucket_secs = bucket_days.saturating_mul(86_400);
+    let mut counts: BTreeMap<i64, usize> = BTreeMap::new();
+    for action in actions {
+        let ahead = (action.next_action_time - now).num_seconds();
+        *counts.entry(ahead.div_euclid(bucket_secs) * bucket_days).or_default() += 1;
+    }
+    counts.into_iter().collect()
+}
+
+/// Earliest next_action_time still after `now`, i.e. when a scheduler should next wake up.
+/// Returns None when every action is already due.
+pub fn next_wakeup(actions: &[Action], now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    // ---
+    actions.iter().filter(|a| a.next_action_time > now).min().map(|a| a.next_action_time)
+}
+
+/// Unions two already-filtered result sets, e.g. from passes with different configs.
+/// Actions sharing an entity_id are resolved per `strategy` (`b` counts as seen after `a`)
+/// and the result is sorted by priority.
+pub fn merge_results(a: Vec<Action>, b: Vec<Action>, strategy: DedupStrategy) -> Vec<Action> {
+    // ---
+    let config = FilterConfig { dedup_strategy: strategy, ..Default::default() };
+    let combined: Vec<Indexed> = a.into_iter().chain(b).enumerate().collect();
+    // `now` only matters under dedup_recency_days, which the default config leaves off
+    let (merged, _) = resolve(combined, DateTime::UNIX_EPOCH, &config);
+    merged.into_iter().map(|(_, action)| action).collect()
+}
+
+/// Applies `process_actions`, then hands the sorted survivors to `transform`,
+/// e.g. to attach computed fields before serialization
+pub fn process_actions_then<F: Fn(Vec<Action>) -> Vec<Action>>(
+    input: Vec<Action>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+    transform: F,
+) -> Vec<Action> {
+    // ---
+    transform(process_actions(input, now, config))
+}
+
+/// Deduplicates (or limits per entity) and sorts records that passed the per-record
+/// filters, also returning the audit trail of the deduplication step
+fn resolve(
+    filtered: Vec<Indexed>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> (Vec<Indexed>, Vec<DedupAuditEntry>) {
+    // ---
+    let (mut survivors, audit) = match config.per_entity_limit {
+        Some(limit) => (keep_top_per_key(filtered, limit, now, config), Vec::new()),
+        None => dedup(filtered, now, config),
+    };
+    if let Some(window) = config.near_duplicate_window_secs {
+        let window = Duration::try_seconds(window).unwrap_or(Duration::MAX);
+        survivors = collapse_near_duplicates(survivors, window, config);
+    }
+    sort_survivors(&mut survivors, config.sort_key, config);
+    (survivors, audit)
+}
+
+/// Re-orders an already-filtered list by `key` without re-running the filters,
+/// e.g. to present a cached result in a different order
+pub fn sort_actions(actions: &mut Vec<Action>, key: SortKey, config: &FilterConfig) {
+    // ---
+    let mut indexed: Vec<Indexed> = actions.drain(..).enumerate().collect();
+    sort_survivors(&mut indexed, key, config);
+    actions.extend(indexed.into_iter().map(|(_, action)| action));
+}
+
+/// Weighted priority score, higher first. Recency is measured from the epoch rather
+/// than from `now`; the shift is the same for every action so the order is unaffected.
+fn priority_score(action: &Action, recency_boost: f64, config: &FilterConfig) -> f64 {
+    // ---
+    let weight = if priority_rank(&action.priority, config) == 0 { 1.0 } else { 0.0 };
+    let days = action.last_action_time.timestamp() as f64 / 86_400.0;
+    weight + recency_boost * days
+}
+
+/// Sets the priority named for the action's entity_id in `config.priority_overrides`,
+/// recording the change in `audit` when it differs
+fn apply_priority_override(
+    mut action: Action,
+    config: &FilterConfig,
+    audit: &mut Vec<PriorityOverride>,
+) -> Action {
+    // ---
+    if let Some(to) = config.priority_overrides.get(&action.entity_id) {
+        if *to != action.priority {
+            let from = std::mem::replace(&mut action.priority, to.clone());
+            audit.push(PriorityOverride {
+                entity_id: action.entity_id.clone(),
+                from,
+                to: to.clone(),
+            });
+        }
+    }
+    action
+}
+
+/// Raises the priority of an action ranked below `config.collapse_below` to that tier
+fn collapse_priority(mut action: Action, config: &FilterConfig) -> Action {
+    // ---
+    if let Some(floor) = &config.collapse_below {
+        if priority_rank(&action.priority, config) > priority_rank(floor, config) {
+            action.priority = floor.clone();
+        }
+    }
+    action
+}
+
+/// Position of `priority` in the sort order: per `config.priority_order` when set,
+/// otherwise Urgent (0) before Normal (1)
+fn priority_rank(priority: &Priority, config: &FilterConfig) -> usize {
+    // ---
+    let derived = match priority {
+        Priority::Urgent => 0,
+        Priority::Normal => 1,
+    };
+    let order = &config.priority_order;
+    if order.is_empty() {
+        return derived;
+    }
+    order.iter().position(|p| p == priority).unwrap_or(order.len() + derived)
+}
+
+/// Keeps the survivors whose seeded entity_id hash falls within `sample.fraction`,
+/// so a given seed always selects the same entities
+fn apply_sample(survivors: &mut Vec<Indexed>, sample: SampleConfig) {
+    // ---
+    let threshold = sample.fraction.clamp(0.0, 1.0) * u64::MAX as f64;
+    survivors
+        .retain(|(_, action)| (seeded_hash(&action.entity_id, sample.seed) as f64) < threshold);
+}
+
+/// Stable hash of `entity_id` mixed with `seed`
+fn seeded_hash(entity_id: &str, seed: u64) -> u64 {
+    // ---
+    let mut hasher = StableHasher::new();
+    hasher.write(&seed.to_le_bytes());
+    hasher.write_field(entity_id.as_bytes());
+    hasher.finish()
+}
+
+/// Keeps only the first `top_urgent` Urgent and `top_normal` Normal survivors,
+/// then sheds whole tiers per `adaptive_priority`
+fn apply_band_caps(survivors: &mut Vec<Indexed>, config: &FilterConfig) {
+    // ---
+    let (mut urgent, mut normal) = (0, 0);
+    survivors.retain(|(_, action)| {
+        let (seen, cap) = match action.priority {
+            Priority::Urgent => (&mut urgent, config.top_urgent),
+            Priority::Normal => (&mut normal, config.top_normal),
+        };
+        *seen += 1;
+        cap.is_none_or(|cap| *seen <= cap)
+    });
+
+    let Some(AdaptivePriority { max_output }) = config.adaptive_priority else {
+        return;
+    };
+    // Tiers rank as they sort, so a custom `priority_order` sheds its last tier first
+    let rank = |action: &Action| priority_rank(&action.priority, config);
+    while survivors.len() > max_output {
+        let (Some(highest), Some(lowest)) = (
+            survivors.iter().map(|(_, a)| rank(a)).min(),
+            survivors.iter().map(|(_, a)| rank(a)).max(),
+        ) else {
+            break;
+        };
+        if highest == lowest {
+            break; // Only the most important tier is left
+        }
+        survivors.retain(|(_, action)| rank(action) != lowest);
+    }
+}
+
+/// Orders survivors according to `key`
+fn sort_survivors(actions: &mut Vec<Indexed>, key: SortKey, config: &FilterConfig) {
+    // ---
+    // The sorts below are stable, so this order survives among equal keys
+    match config.tie_break {
+        TieBreak::InputOrder => {}
+        TieBreak::Hashed { seed } => {
+            actions.sort_by_cached_key(|(_, action)| seeded_hash(&action.entity_id, seed));
+        }
+        TieBreak::LastActionDesc => actions.sort_by_key(|(_, a)| Reverse(a.last_action_time)),
+    }
+    match key {
+        SortKey::Priority => match config.recency_boost {
+            None => actions.sort_by_key(|(_, a)| priority_rank(&a.priority, config)),
+            Some(boost) => actions.sort_by(|(_, a), (_, b)| {
+                priority_score(b, boost, config).total_cmp(&priority_score(a, boost, config))
+            }),
+        },
+        SortKey::NextActionTime | SortKey::NextActionTimeDesc => {
+            // Times within one comparison unit tie and fall back to entity_id, so
+            // sub-unit jitter does not decide the order
+            let unit = config.comparison_granularity;
+            let descending = key == SortKey::NextActionTimeDesc;
+            actions.sort_by(|(_, a), (_, b)| {
+                let (a_time, b_time) =
+                    (unit.truncate(a.next_action_time), unit.truncate(b.next_action_time));
+                let by_time = if descending { b_time.cmp(&a_time) } else { a_time.cmp(&b_time) };
+                by_time.then_with(|| a.entity_id.cmp(&b.entity_id))
+            })
+        }
+        SortKey::Score => actions.sort_by(|(_, a), (_, b)| {
+            cmp_scores(b.score, a.score).then_with(|| {
+                priority_rank(&a.priority, config).cmp(&priority_rank(&b.priority, config))
+            })
+        }),
+        SortKey::HeapOrder => {
+            // Mirror the scheduler: a min-heap on Action's Ord, drained by repeated pops
+            let mut heap: BinaryHeap<Reverse<(Action, usize)>> =
+                actions.drain(..).map(|(index, action)| Reverse((action, index))).collect();
+            while let Some(Reverse((action, index))) = heap.pop() {
+                actions.push((index, action));
+            }
+        }
+    }
+}
+
+/// Returns the size of the largest group of actions sharing a next_action_time,
+/// warning when it exceeds `threshold`. Diagnostic only; nothing is dropped.
+fn check_shared_next_times(input: &[Action], threshold: usize) -> usize {
+    // ---
+    let mut counts: HashMap<DateTime<Utc>, usize> = HashMap::new();
+    for action in input {
+        *counts.entry(action.next_action_time).or_default() += 1;
+    }
+    let Some((time, largest)) = counts.into_iter().max_by_key(|(_, count)| *count) else {
+        return 0;
+    };
+    if largest > threshold {
+        tracing::warn!(
+            count = largest,
+            threshold,
+            next_action_time = %time,
+            "Many actions share one next_action_time; possible bulk-import bug"
+        );
+    }
+    largest
+}
+
+/// True when `last_action_time` is further ahead of `now` than the configured skew allows
+fn exceeds_future_skew(action: &Action, now: DateTime<Utc>, config: &FilterConfig) -> bool {
+    // ---
+    config
+        .max_future_skew_minutes
+        .map(|max_skew| Duration::try_minutes(max_skew).unwrap_or(Duration::MAX))
+        .is_some_and(|max_skew| action.last_action_time > saturating_add(now, max_skew))
+}
+
+/// Enforces the configured clock-skew limit on `last_action_time`.
+/// Returns None when the action is rejected.
+fn apply_future_skew(
+    mut action: Action,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Option<Action> {
+    // ---
+    if !exceeds_future_skew(&action, now, config) {
+        return Some(action);
+    }
+
+    match config.future_skew_policy {
+        SkewPolicy::Reject => None,
+        SkewPolicy::Clamp => {
+            action.last_action_time = now;
+            Some(action)
+        }
+    }
+}
+
+/// Orders optional scores ascending, with a missing score below every present one
+fn cmp_scores(a: Option<f64>, b: Option<f64>) -> Ordering {
+    // ---
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+/// True when `config.min_score` drops the action
+fn below_min_score(action: &Action, config: &FilterConfig) -> bool {
+    // ---
+    let Some(min) = config.min_score else {
+        return false;
+    };
+    match action.score {
+        Some(score) => score < min,
+        None => config.missing_score_policy == MissingScorePolicy::Drop,
+    }
+}
+
+/// True when `zero_duration_policy` drops the action (last == next)
+fn is_dropped_zero_duration(action: &Action, config: &FilterConfig) -> bool {
+    // ---
+    config.zero_duration_policy == ZeroDurationPolicy::Drop
+        && action.last_action_time == action.next_action_time
+}
+
+/// Every per-record validation problem with `action`, for `ValidationAction::WarnOnly`.
+/// Skew and length violations count only under policies that would reject them.
+fn validation_issues(
+    action: &Action,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Vec<&'static str> {
+    // ---
+    let checks = [
+        ("empty entity_id", action.entity_id.trim().is_empty()),
+        ("malformed entity_id", is_malformed_id(action, config)),
+        (
+            "entity_id too long",
+            exceeds_entity_id_len(action, config)
+                && config.entity_id_len_policy == LengthPolicy::Reject,
+        ),
+        (
+            "last_action_time too far in the future",
+            exceeds_future_skew(action, now, config)
+                && config.future_skew_policy == SkewPolicy::Reject,
+        ),
+        ("zero duration", is_dropped_zero_duration(action, config)),
+    ];
+    checks.into_iter().filter(|(_, failed)| *failed).map(|(issue, _)| issue).collect()
+}
+
+/// True when a non-empty entity_id does not match `config.entity_id_pattern`
+fn is_malformed_id(action: &Action, config: &FilterConfig) -> bool {
+    // ---
+    !action.entity_id.trim().is_empty()
+        && config
+            .entity_id_regex()
+            .ok()
+            .flatten()
+            .is_some_and(|format| !format.is_match(&action.entity_id))
+}
+
+/// True when the entity_id is longer than the configured limit
+fn exceeds_entity_id_len(action: &Action, config: &FilterConfig) -> bool {
+    // ---
+    config.max_entity_id_len.is_some_and(|max| action.entity_id.chars().count() > max)
+}
+
+/// Enforces the configured entity_id length limit.
+/// Returns None when the action is rejected.
+fn apply_entity_id_limit(mut action: Action, config: &FilterConfig) -> Option<Action> {
+    // ---
+    let Some(max) = config.max_entity_id_len.filter(|_| exceeds_entity_id_len(&action, config))
+    else {
+        return Some(action);
+    };
+
+    match config.entity_id_len_policy {
+        LengthPolicy::Reject => None,
+        LengthPolicy::Truncate => {
+            let original_len = action.entity_id.chars().count();
+            action.entity_id = action.entity_id.chars().take(max).collect();
+            tracing::warn!(
+                entity_id = %action.entity_id,
+                original_len,
+                max,
+                "Truncated over-long entity_id"
+            );
+            Some(action)
+        }
+    }
+}
+
+/// Collapses actions sharing a dedup key down to a single survivor.
+/// Survivors keep the position of the first occurrence of their key.
+/// Under `dedup_recency_days`, older records form a separate pool with its own survivors.
+/// Also returns, in survivor order, an audit entry for each key that had duplicates.
+fn dedup(
+    actions: Vec<Indexed>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> (Vec<Indexed>, Vec<DedupAuditEntry>) {
+    // ---
+    let recent_since = recent_since(now, config);
+    let mut slots: HashMap<(bool, String), usize> = HashMap::new();
+    let mut survivors: Vec<Indexed> = Vec::new();
+    let mut dropped: Vec<Vec<usize>> = Vec::new(); // Parallel to survivors
+    let mut conflicts = 0;
+    let mut conflict_examples: Vec<(String, Priority, Priority)> = Vec::new();
+
+    for (index, mut action) in actions {
+        let key = pool_key(&action, recent_since, config);
+        match slots.get(&key) {
+            Some(&slot) => {
+                let current = &survivors[slot].1;
+                if current.priority != action.priority {
+                    conflicts += 1;
+                    if conflict_examples.len() < CONFLICT_EXAMPLES
+                        && !conflict_examples.iter().any(|(id, ..)| *id == action.entity_id)
+                    {
+                        conflict_examples.push((
+                            action.entity_id.clone(),
+                            current.priority.clone(),
+                            action.priority.clone(),
+                        ));
+                    }
+                }
+                let keep_new = replaces(config, current, &action);
+                if config.log_dedup_details {
+                    let (survivor, dropped) = if keep_new {
+                        (&action, &survivors[slot].1)
+                    } else {
+                        (&survivors[slot].1, &action)
+                    };
+                    tracing::debug!(
+                        entity_id = %dropped.entity_id,
+                        surviving_priority = ?survivor.priority,
+                        differing_fields = ?differing_fields(survivor, dropped),
+                        "dropped duplicate"
+                    );
+                }
+                if keep_new {
+                    match config.dedup_strategy {
+                        DedupStrategy::Merge => {
+                            let earlier = std::mem::take(&mut survivors[slot].1.tags);
+                            action.tags = union_tags(earlier, action.tags);
+                        }
+                        DedupStrategy::SmartMerge => {
+                            action =
+                                smart_merge(std::mem::take(&mut survivors[slot].1), action, config);
+                        }
+                        _ => {}
+                    }
+                    dropped[slot].push(survivors[slot].0);
+                    survivors[slot] = (index, action);
+                } else {
+                    dropped[slot].push(index);
+                }
+            }
+            None => {
+                slots.insert(key, survivors.len());
+                survivors.push((index, action));
+                dropped.push(Vec::new());
+            }
+        }
+    }
+    if conflicts > 0 {
+        // Conflicting duplicates usually point at an upstream inconsistency; one line per
+        // run keeps large batches from flooding the logs
+        let examples: Vec<String> = conflict_examples
+            .iter()
+            .map(|(id, kept, incoming)| {
+                format!("{id} (kept_so_far={kept:?}, incoming={incoming:?})")
+            })
+            .collect();
+        tracing::warn!(conflicts, ?examples, "Duplicate entity_ids with conflicting priorities");
+    }
+
+    let audit = survivors
+        .iter()
+        .zip(dropped)
+        .filter(|(_, dropped_indices)| !dropped_indices.is_empty())
+        .map(|((kept_index, action), mut dropped_indices)| {
+            dropped_indices.sort_unstable();
+            DedupAuditEntry {
+                entity_id: action.entity_id.clone(),
+                kept_index: *kept_index,
+                dropped_indices,
+            }
+        })
+        .collect();
+    (survivors, audit)
+}
+
+/// Keeps the `limit` occurrences of each dedup key that `dedup_strategy` ranks highest
+/// (see `occurrence_rank`), in input order
+fn keep_top_per_key(
+    actions: Vec<Indexed>,
+    limit: usize,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Vec<Indexed> {
+    // ---
+    let recent_since = recent_since(now, config);
+    let mut groups: HashMap<(bool, String), Vec<usize>> = HashMap::new();
+    for (position, (_, action)) in actions.iter().enumerate() {
+        groups.entry(pool_key(action, recent_since, config)).or_default().push(position);
+    }
+
+    let mut keep = vec![false; actions.len()];
+    for mut group in groups.into_values() {
+        group.sort_by_cached_key(|&position| {
+            let (index, action) = &actions[position];
+            Reverse(occurrence_rank(config, action, *index))
+        });
+        for position in group.into_iter().take(limit) {
+            keep[position] = true;
+        }
+    }
+    actions.into_iter().zip(keep).filter(|(_, kept)| *kept).map(|(action, _)| action).collect()
+}
+
+/// Strength of one occurrence among its duplicates, as (merit, tie merit, input index): with
+/// a single survivor the strongest is the one `replaces` would leave standing (later ones win
+/// ties)
+fn occurrence_rank(config: &FilterConfig, action: &Action, index: usize) -> (i64, i64, usize) {
+    // ---
+    // Microseconds cover chrono's whole date range, so the negation cannot overflow
+    let earliness = -action.next_action_time.timestamp_micros();
+    let (merit, tie_merit) = match config.dedup_strategy {
+        DedupStrategy::LastSeen | DedupStrategy::Merge | DedupStrategy::SmartMerge => (0, 0),
+        DedupStrategy::MostComplete => (action.completeness() as i64, 0),
+        DedupStrategy::HighestPriority => {
+            // Earlier in the priority order is stronger
+            let urgency = -(priority_rank(&action.priority, config) as i64);
+            match config.priority_tie_break {
+                PriorityTieBreak::LastSeen => (urgency, 0),
+                PriorityTieBreak::EarliestNext => (urgency, earliness),
+            }
+        }
+        DedupStrategy::EarliestNext => (earliness, 0),
+    };
+    (merit, tie_merit, index)
+}
+
+/// Start of the recent pool under `dedup_recency_days`
+fn recent_since(now: DateTime<Utc>, config: &FilterConfig) -> Option<DateTime<Utc>> {
+    // ---
+    config
+        .dedup_recency_days
+        .map(|days| saturating_add(now, -Duration::try_days(days).unwrap_or(Duration::MAX)))
+}
+
+/// Dedup key of `action` qualified by whether it falls in the older recency pool
+fn pool_key(
+    action: &Action,
+    recent_since: Option<DateTime<Utc>>,
+    config: &FilterConfig,
+) -> (bool, String) {
+    // ---
+    let older = recent_since.is_some_and(|since| action.last_action_time < since);
+    (older, dedup_key(action, config))
+}
+
+/// Collapses same-entity actions whose next_action_time fall within `window` of an
+/// earlier survivor, keeping the one first in the priority order (the earlier one on ties)
+fn collapse_near_duplicates(
+    actions: Vec<Indexed>,
+    window: Duration,
+    config: &FilterConfig,
+) -> Vec<Indexed> {
+    // ---
+    let mut kept: Vec<Indexed> = Vec::with_capacity(actions.len());
+    let mut by_entity: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for (index, action) in actions {
+        let slots = by_entity.entry(action.entity_id.clone()).or_default();
+        let near = slots.iter().copied().find(|&slot| {
+            (kept[slot].1.next_action_time - action.next_action_time).abs() <= window
+        });
+
+        match near {
+            Some(slot) => {
+                let rank = |action: &Action| priority_rank(&action.priority, config);
+                if rank(&action) < rank(&kept[slot].1) {
+                    kept[slot] = (index, action);
+                }
+            }
+            None => {
+                slots.push(kept.len());
+                kept.push((index, action));
+            }
+        }
+    }
+    kept
+}
+
+/// Computes the key under which `action` is deduplicated
+pub(crate) fn dedup_key(action: &Action, config: &FilterConfig) -> String {
+    // ---
+    match &config.dedup_by {
+        DedupKey::EntityId if config.case_insensitive_ids => action.entity_id.to_lowercase(),
+        DedupKey::EntityId => action.entity_id.clone(),
+        // Content keys compare the canonical form; whole records must be byte-identical
+        DedupKey::ContentHash { fields } => content_hash(&action.canonical(), Some(fields)),
+        DedupKey::WholeRecord => content_hash(action, None),
+    }
+}
+
+/// Stable hash over the named serialized fields of `action`, or over all of them except
+/// the opaque `metadata` passthrough. Missing fields hash as null.
+fn content_hash(action: &Action, fields: Option<&[String]>) -> String {
+    // ---
+    let record = match serde_json::to_value(action) {
+        Ok(Value::Object(record)) => record,
+        _ => Default::default(),
+    };
+    let names: Vec<&String> = match fields {
+        Some(fields) => fields.iter().collect(),
+        // Keys follow the struct field order (serde_json preserve_order), so this is stable
+        None => record.keys().filter(|name| *name != "metadata").collect(),
+    };
+
+    let mut hasher = StableHasher::new();
+    for name in names {
+        let value = record.get(name).unwrap_or(&Value::Null);
+        hasher.write_field(name.as_bytes());
+        hasher.write_field(value.to_string().as_bytes());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Names of the fields on which two occurrences disagree
+fn differing_fields(a: &Action, b: &Action) -> Vec<&'static str> {
+    // ---
+    let checks = [
+        ("entity_id", a.entity_id != b.entity_id),
+        ("last_action_time", a.last_action_time != b.last_action_time),
+        ("next_action_time", a.next_action_time != b.next_action_time),
+        ("priority", a.priority != b.priority),
+        ("notes", a.notes != b.notes),
+        ("tags", a.tags != b.tags),
+        ("created_at", a.created_at != b.created_at),
+        ("score", a.score != b.score),
+    ];
+    checks.into_iter().filter(|(_, differs)| *differs).map(|(name, _)| name).collect()
+}
+
+/// Appends the tags of `later` not already in `earlier`, preserving first-seen order
+fn union_tags(mut earlier: Vec<String>, later: Vec<String>) -> Vec<String> {
+    // ---
+    for tag in later {
+        if !earlier.contains(&tag) {
+            earlier.push(tag);
+        }
+    }
+    earlier
+}
+
+/// Folds `earlier` into `later` per `DedupStrategy::SmartMerge`
+fn smart_merge(earlier: Action, mut later: Action, config: &FilterConfig) -> Action {
+    // ---
+    later.tags = union_tags(earlier.tags, later.tags);
+    later.next_action_time = later.next_action_time.min(earlier.next_action_time);
+    later.last_action_time = later.last_action_time.max(earlier.last_action_time);
+    if priority_rank(&earlier.priority, config) < priority_rank(&later.priority, config) {
+        later.priority = earlier.priority;
+    }
+    later
+}
+
+/// Returns true when `candidate` (seen later) should replace `current`
+fn replaces(config: &FilterConfig, current: &Action, candidate: &Action) -> bool {
+    // ---
+    match config.dedup_strategy {
+        // Last occurrence wins (carrying what the merging strategies fold in)
+        DedupStrategy::LastSeen | DedupStrategy::Merge | DedupStrategy::SmartMerge => true,
+        DedupStrategy::MostComplete => candidate.completeness() >= current.completeness(),
+        DedupStrategy::HighestPriority => match config.priority_tie_break {
+            _ if candidate.priority != current.priority => {
+                priority_rank(&candidate.priority, config)
+                    < priority_rank(&current.priority, config)
+            }
+            PriorityTieBreak::LastSeen => true,
+            PriorityTieBreak::EarliestNext => {
+                candidate.next_action_time <= current.next_action_time
+            }
+        },
+        DedupStrategy::EarliestNext => candidate <= current,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Priority;
+    use crate::test_support::capture_logs;
+    use anyhow::{ensure, Result};
+
+    /// Helper function to parse RFC3339 date strings for tests
+    fn parse_date(s: &str) -> Result<DateTime<Utc>> {
+        // ---
+        let temp = DateTime::parse_from_rfc3339(s)?;
+        Ok(temp.with_timezone(&Utc))
+    }
+
+    /// Fixed reference time for tests that use literal dates
+    fn reference_now() -> Result<DateTime<Utc>> {
+        // ---
+        parse_date("2025-06-28T00:00:00Z")
+    }
+
+    #[test]
+    fn test_passes_time_filters_at_boundaries() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let config = FilterConfig::default();
+        let action = |last_days_ago: i64, next_days: i64| Action {
+            entity_id: "boundary".to_string(),
+            last_action_time: now - Duration::days(last_days_ago),
+            next_action_time: now + Duration::days(next_days),
+            ..Default::default()
+        };
+
+        ensure!(passes_time_filters(&action(8, 90), now, &config), "Expected 90 days to pass");
+        ensure!(!passes_time_filters(&action(8, 91), now, &config), "Expected 91 days to fail");
+        ensure!(!passes_time_filters(&action(7, 10), now, &config), "Expected 7 days ago to fail");
+        ensure!(
+            passes_time_filters(
+                &Action { entity_id: String::new(), ..action(8, 10) },
+                now,
+                &config
+            ),
+            "Expected the predicate to ignore validation rules"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_business_days_stretch_staleness_window() -> Result<()> {
+        // ---
+        let now = parse_date("2025-07-09T00:00:00Z")?; // Wednesday
+        let action = Action {
+            entity_id: "over_weekend".to_string(),
+            last_action_time: parse_date("2025-06-30T00:00:00Z")?, // 9 calendar days earlier
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+
+        ensure!(
+            passes_time_filters(&action, now, &FilterConfig::default()),
+            "Expected 9 calendar days to clear the 7-day window"
+        );
+        let config = FilterConfig { use_business_days: true, ..Default::default() };
+        ensure!(
+            !passes_time_filters(&action, now, &config),
+            "Expected 7 business days to sit on the (exclusive) boundary"
+        );
+        let older = Action { last_action_time: parse_date("2025-06-27T00:00:00Z")?, ..action };
+        ensure!(passes_time_filters(&older, now, &config), "Expected 8 business days to pass");
+
+        let holidays = Some(["2025-07-04".parse()?].into());
+        let config = FilterConfig { holidays, ..config };
+        ensure!(
+            !passes_time_filters(&older, now, &config),
+            "Expected a holiday inside the window to move the boundary back a day"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_and_sort_actions() -> Result<()> {
+        // ---
+        let input = vec![
+            Action {
+                entity_id: "entity_1".to_string(),
+                last_action_time: parse_date("2025-06-20T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-10T00:00:00Z")?,
+                priority: Priority::Urgent,
+                ..Default::default()
+            },
+            Action {
+                entity_id: "entity_2".to_string(),
+                last_action_time: parse_date("2025-06-01T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
+                priority: Priority::Normal,
+                ..Default::default()
+            },
+            Action {
+                entity_id: "entity_3".to_string(),
+                last_action_time: parse_date("2025-03-01T00:00:00Z")?,
+                next_action_time: parse_date("2026-01-01T00:00:00Z")?,
+                priority: Priority::Urgent, // should be excluded (next_action too far)
+                ..Default::default()
+            },
+            Action {
+                entity_id: "entity_4".to_string(),
+                last_action_time: parse_date("2025-06-25T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-10T00:00:00Z")?,
+                priority: Priority::Urgent, // should be excluded (last_action < 7 days ago)
+                ..Default::default()
+            },
+        ];
+
+        let output = process_actions(input, reference_now()?, &FilterConfig::default());
+
+        // Verify we have exactly 2 actions after filtering
+        ensure!(output.len() == 2, "Expected 2 actions after filtering, got {}", output.len());
+
+        // Verify the complete order: Urgent priority comes first, then Normal
+        ensure!(
+            output[0].entity_id == "entity_1",
+            "Expected first action to be entity_1, got {}",
+            output[0].entity_id
+        );
+        ensure!(
+            output[0].priority == Priority::Urgent,
+            "Expected first action to have Urgent priority, got {:?}",
+            output[0].priority
+        );
+
+        ensure!(
+            output[1].entity_id == "entity_2",
+            "Expected second action to be entity_2, got {}",
+            output[1].entity_id
+        );
+        ensure!(
+            output[1].priority == Priority::Normal,
+            "Expected second action to have Normal priority, got {:?}",
+            output[1].priority
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deduplication_with_priority_conflict() -> Result<()> {
+        // ---
+        let input = vec![
+            Action {
+                entity_id: "duplicate".to_string(),
+                last_action_time: parse_date("2025-05-01T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
+                priority: Priority::Normal,
+                ..Default::default()
+            },
+            Action {
+                entity_id: "duplicate".to_string(),
+                last_action_time: parse_date("2025-05-01T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
+                priority: Priority::Urgent,
+                ..Default::default()
+            },
+        ];
+
+        let output = process_actions(input, reference_now()?, &FilterConfig::default());
+        ensure!(
+            output[0].entity_id == "duplicate",
+            "Expected action to be for entity 'duplicate', got {}",
+            output[0].entity_id
+        );
+
+        // Currently keeps last seen, so should be Urgent
+        ensure!(
+            output[0].priority == Priority::Urgent,
+            "Expected single remaining item to be Urgent"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_conflict_is_warned() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |priority: Priority| Action {
+            entity_id: "duplicate".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+
+        for strategy in [DedupStrategy::LastSeen, DedupStrategy::HighestPriority] {
+            let input = vec![action(Priority::Urgent), action(Priority::Normal)];
+            let config = FilterConfig { dedup_strategy: strategy, ..Default::default() };
+            let (_, logs) =
+                capture_logs(tracing::Level::WARN, || process_actions(input, now, &config));
+            ensure!(
+                logs.contains("conflicting priorities")
+                    && logs.contains("duplicate (kept_so_far=Urgent, incoming=Normal)"),
+                "Expected the conflicting pair at warn under {strategy:?}: {logs}"
+            );
+        }
+
+        // Many conflicting duplicates still produce a single warning
+        let input: Vec<Action> = (0..50)
+            .flat_map(|i| {
+                let id = format!("entity_{i}");
+                [
+                    Action { entity_id: id.clone(), ..action(Priority::Urgent) },
+                    Action { entity_id: id, ..action(Priority::Normal) },
+                ]
+            })
+            .collect();
+        let (_, logs) = capture_logs(tracing::Level::WARN, || {
+            process_actions(input, now, &FilterConfig::default())
+        });
+        let warnings = logs.lines().filter(|line| line.contains("conflicting priorities")).count();
+        ensure!(warnings == 1, "Expected one aggregated warning, got {warnings}: {logs}");
+        ensure!(
+            logs.contains("conflicts=50")
+                && logs.contains("entity_0")
+                && !logs.contains("entity_3\""),
+            "Expected the count and three examples: {logs}"
+        );
+
+        let input = vec![action(Priority::Normal), action(Priority::Normal)];
+        let (_, logs) = capture_logs(tracing::Level::WARN, || {
+            process_actions(input, now, &FilterConfig::default())
+        });
+        ensure!(!logs.contains("conflicting priorities"), "Expected no warning: {logs}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_most_complete_keeps_richest_record() -> Result<()> {
+        // ---
+        let input = vec![
+            Action {
+                entity_id: "duplicate".to_string(),
+                last_action_time: parse_date("2025-05-01T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
+                priority: Priority::Normal,
+                notes: Some("call back after 5pm".to_string()),
+                ..Default::default()
+            },
+            Action {
+                entity_id: "duplicate".to_string(),
+                last_action_time: parse_date("2025-05-02T00:00:00Z")?,
+                next_action_time: parse_date("2025-07-02T00:00:00Z")?,
+                priority: Priority::Urgent,
+                ..Default::default()
+            },
+        ];
+
+        let config =
+            FilterConfig { dedup_strategy: DedupStrategy::MostComplete, ..Default::default() };
+        let output = process_actions(input, reference_now()?, &config);
+
+        ensure!(output.len() == 1, "Expected 1 action after dedup, got {}", output.len());
+        ensure!(
+            output[0].notes.as_deref() == Some("call back after 5pm"),
+            "Expected the occurrence with notes to survive, got {:?}",
+            output[0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_earliest_next_keeps_soonest() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |next_days: i64| Action {
+            entity_id: "duplicate".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            ..Default::default()
+        };
+        let input = vec![action(5), action(20)];
+
+        let config =
+            FilterConfig { dedup_strategy: DedupStrategy::EarliestNext, ..Default::default() };
+        let output = process_actions(input.clone(), now, &config);
+        ensure!(output == [action(5)], "Expected the earlier occurrence, got {output:?}");
+
+        let output = process_actions(input, now, &FilterConfig::default());
+        ensure!(output == [action(20)], "Expected the last occurrence by default, got {output:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_highest_priority_ties_keep_earliest_next() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |next_days: i64| Action {
+            entity_id: "duplicate".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            priority: Priority::Urgent,
+            ..Default::default()
+        };
+        let config = FilterConfig {
+            dedup_strategy: DedupStrategy::HighestPriority,
+            priority_tie_break: PriorityTieBreak::EarliestNext,
+            ..Default::default()
+        };
+
+        for input in [vec![action(5), action(20)], vec![action(20), action(5)]] {
+            let output = process_actions(input, now, &config);
+            ensure!(output == [action(5)], "Expected the earliest-next occurrence, got {output:?}");
+        }
+
+        let default_ties =
+            FilterConfig { dedup_strategy: DedupStrategy::HighestPriority, ..Default::default() };
+        let output = process_actions(vec![action(5), action(20)], now, &default_ties);
+        ensure!(output == [action(20)], "Expected the last occurrence by default, got {output:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_merge_unions_tags() -> Result<()> {
+        // ---
+        let action = |next: &str, tags: &[&str]| -> Result<Action> {
+            Ok(Action {
+                entity_id: "duplicate".to_string(),
+                last_action_time: parse_date("2025-05-01T00:00:00Z")?,
+                next_action_time: parse_date(next)?,
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            })
+        };
+        let input = vec![
+            action("2025-07-01T00:00:00Z", &["billing", "vip"])?,
+            action("2025-07-02T00:00:00Z", &["churn_risk", "vip"])?,
+        ];
+
+        let config = FilterConfig { dedup_strategy: DedupStrategy::Merge, ..Default::default() };
+        let output = process_actions(input, reference_now()?, &config);
+
+        ensure!(output.len() == 1, "Expected 1 action after dedup, got {}", output.len());
+        ensure!(
+            output[0].tags == ["billing", "vip", "churn_risk"],
+            "Expected the combined tags, got {:?}",
+            output[0].tags
+        );
+        ensure!(
+            output[0].next_action_time == parse_date("2025-07-02T00:00:00Z")?,
+            "Expected the last occurrence's other fields to survive"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_smart_merge_combines_all_rules() -> Result<()> {
+        // ---
+        let action = |priority, last: &str, next: &str, tags: &[&str]| -> Result<Action> {
+            Ok(Action {
+                entity_id: "duplicate".to_string(),
+                last_action_time: parse_date(last)?,
+                next_action_time: parse_date(next)?,
+                priority,
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            })
+        };
+        let input = vec![
+            action(Priority::Urgent, "2025-05-01T00:00:00Z", "2025-07-10T00:00:00Z", &["a"])?,
+            action(Priority::Normal, "2025-06-10T00:00:00Z", "2025-07-05T00:00:00Z", &["b", "a"])?,
+            action(Priority::Normal, "2025-05-20T00:00:00Z", "2025-07-02T00:00:00Z", &["c"])?,
+        ];
+
+        let config =
+            FilterConfig { dedup_strategy: DedupStrategy::SmartMerge, ..Default::default() };
+        let output = process_actions(input, reference_now()?, &config);
+
+        ensure!(output.len() == 1, "Expected 1 action after dedup, got {}", output.len());
+        let survivor = &output[0];
+        ensure!(
+            survivor.tags == ["a", "b", "c"],
+            "Expected the tag union, got {:?}",
+            survivor.tags
+        );
+        ensure!(
+            survivor.next_action_time == parse_date("2025-07-02T00:00:00Z")?,
+            "Expected the earliest next_action_time, got {}",
+            survivor.next_action_time
+        );
+        ensure!(
+            survivor.last_action_time == parse_date("2025-06-10T00:00:00Z")?,
+            "Expected the latest last_action_time, got {}",
+            survivor.last_action_time
+        );
+        ensure!(survivor.priority == Priority::Urgent, "Expected the highest priority");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_subset_detects_fabrication() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, days_ahead: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(days_ahead),
+            ..Default::default()
+        };
+        let input = vec![action("dup", 5), action("single", 6), action("dup", 7)];
+
+        let output = process_actions(input.clone(), now, &FilterConfig::default());
+        ensure!(output.len() == 2, "Expected 2 actions after dedup, got {}", output.len());
+        assert_output_subset(&input, &output).map_err(anyhow::Error::msg)?;
+
+        let mut fabricated = output;
+        fabricated[1].next_action_time += Duration::days(1);
+        let Err(message) = assert_output_subset(&input, &fabricated) else {
+            anyhow::bail!("Expected the altered record to be reported");
+        };
+        ensure!(message.contains("output[1]"), "Expected the position in {message:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_is_subset_of_input_for_passthrough_configs() -> Result<()> {
+        // ---
+        let mut input: Vec<Action> =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        input.push(Action { score: Some(f64::NAN), ..input[0].clone() });
+
+        for dedup_strategy in [
+            DedupStrategy::LastSeen,
+            DedupStrategy::MostComplete,
+            DedupStrategy::HighestPriority,
+            DedupStrategy::EarliestNext,
+        ] {
+            let config = FilterConfig { dedup_strategy, ..Default::default() };
+            let output = process_actions(input.clone(), reference_now()?, &config);
+            ensure!(!output.is_empty(), "Expected survivors under {dedup_strategy:?}");
+            assert_output_subset(&input, &output).map_err(anyhow::Error::msg)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_recency_keeps_fresh_record() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |days_ago: i64| Action {
+            entity_id: "entity".to_string(),
+            last_action_time: now - Duration::days(days_ago),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action(10), action(40)];
+
+        let output = process_actions(input.clone(), now, &FilterConfig::default());
+        ensure!(output == [action(40)], "Expected the last occurrence to win by default");
+
+        let config = FilterConfig { dedup_recency_days: Some(30), ..Default::default() };
+        let output = process_actions(input, now, &config);
+        ensure!(output.contains(&action(10)), "Expected the fresh record to survive");
+        ensure!(output.len() == 2, "Expected the old record to dedup separately");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_score_drops_low_scores() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, score: Option<f64>| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            score,
+            ..Default::default()
+        };
+        let input =
+            vec![action("low", Some(0.3)), action("high", Some(0.8)), action("unscored", None)];
+
+        let output = process_actions(input.clone(), now, &FilterConfig::default());
+        ensure!(output.len() == 3, "Expected every action without min_score, got {output:?}");
+
+        let config = FilterConfig { min_score: Some(0.5), ..Default::default() };
+        let (output, report) = process_actions_with_report(input.clone(), now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["high", "unscored"], "Expected the low score dropped, got {ids:?}");
+        ensure!(report.low_score == 1, "Expected 1 low_score drop, got {}", report.low_score);
+
+        let config = FilterConfig { missing_score_policy: MissingScorePolicy::Drop, ..config };
+        let output = process_actions(input, now, &config);
+        ensure!(
+            output == [action("high", Some(0.8))],
+            "Expected only the high score, got {output:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extreme_timestamps_do_not_panic() -> Result<()> {
+        // ---
+        let far_future = Action {
+            entity_id: "far_future".to_string(),
+            last_action_time: reference_now()? - Duration::days(10),
+            next_action_time: DateTime::<Utc>::MAX_UTC,
+            ..Default::default()
+        };
+        let output =
+            process_actions(vec![far_future.clone()], reference_now()?, &FilterConfig::default());
+        ensure!(output.is_empty(), "Expected the far-future action to be dropped");
+
+        // The 90-day bound of a `now` this late is not representable
+        let now = DateTime::<Utc>::MAX_UTC - Duration::days(30);
+        let input = vec![Action { last_action_time: now - Duration::days(10), ..far_future }];
+        let config = FilterConfig {
+            max_future_skew_minutes: Some(i64::MAX),
+            dedup_recency_days: Some(i64::MAX),
+            near_duplicate_window_secs: Some(i64::MAX),
+            ..Default::default()
+        };
+        let output = process_actions(input.clone(), now, &config);
+        ensure!(output == input, "Expected the action kept under a saturated window");
+
+        let business = FilterConfig { use_business_days: true, ..config };
+        let output = process_actions(input.clone(), now, &business);
+        ensure!(output == input, "Expected business-day windows to saturate too");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_results_keeps_most_urgent() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let a = vec![action("shared", Priority::Urgent), action("only_a", Priority::Normal)];
+        let b = vec![action("shared", Priority::Normal), action("only_b", Priority::Urgent)];
+
+        let merged = merge_results(a.clone(), b.clone(), DedupStrategy::HighestPriority);
+        let ids: Vec<(&str, &Priority)> =
+            merged.iter().map(|a| (a.entity_id.as_str(), &a.priority)).collect();
+        ensure!(
+            ids == [
+                ("shared", &Priority::Urgent),
+                ("only_b", &Priority::Urgent),
+                ("only_a", &Priority::Normal)
+            ],
+            "Unexpected merge {ids:?}"
+        );
+
+        let merged = merge_results(a, b, DedupStrategy::LastSeen);
+        ensure!(
+            merged.iter().any(|a| a.entity_id == "shared" && a.priority == Priority::Normal),
+            "Expected the later set to win under LastSeen"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_adaptive_priority_drops_whole_tiers() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: String, priority: Priority| Action {
+            entity_id: id,
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let mut input: Vec<Action> =
+            (0..20).map(|i| action(format!("normal_{i}"), Priority::Normal)).collect();
+        input.extend((0..3).map(|i| action(format!("urgent_{i}"), Priority::Urgent)));
+
+        let config = FilterConfig {
+            adaptive_priority: Some(AdaptivePriority { max_output: 5 }),
+            ..Default::default()
+        };
+        let (output, report) = process_actions_with_report(input.clone(), now, &config);
+        ensure!(output.len() == 3, "Expected only the 3 Urgents, got {}", output.len());
+        ensure!(output.iter().all(|a| a.priority == Priority::Urgent), "Expected no Normals");
+        ensure!(report.capped == 20, "Expected 20 capped, got {}", report.capped);
+
+        let roomy = FilterConfig {
+            adaptive_priority: Some(AdaptivePriority { max_output: 23 }),
+            ..Default::default()
+        };
+        let output = process_actions(input.clone(), now, &roomy);
+        ensure!(output.len() == 23, "Expected everything within budget, got {}", output.len());
+
+        let normal_first = FilterConfig {
+            adaptive_priority: Some(AdaptivePriority { max_output: 5 }),
+            priority_order: vec![Priority::Normal, Priority::Urgent],
+            ..Default::default()
+        };
+        let output = process_actions(input, now, &normal_first);
+        ensure!(output.len() == 20, "Expected only the 20 Normals, got {}", output.len());
+        ensure!(
+            output.iter().all(|a| a.priority == Priority::Normal),
+            "Expected the tier sorting last (Urgent) to be shed"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_warn_only_keeps_invalid_records() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action("valid"), action("  ")];
+
+        let enforced = process_actions(input.clone(), now, &FilterConfig::default());
+        ensure!(enforced == [action("valid")], "Expected the empty id dropped by default");
+
+        let config = FilterConfig {
+            validation_action: ValidationAction::WarnOnly,
+            empty_id_policy: EmptyIdPolicy::Error,
+            ..Default::default()
+        };
+        let (result, logs) =
+            capture_logs(tracing::Level::WARN, || try_process_actions(input.clone(), now, &config));
+        let (output, report) = result?;
+        ensure!(output == input, "Expected every record kept, got {output:?}");
+        ensure!(report.validation == 0, "Expected no validation drops, got {}", report.validation);
+        ensure!(
+            logs.contains("Validation issue") && logs.contains("empty entity_id"),
+            "Expected a warning naming the issue, got {logs:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_per_entity_limit_keeps_top_k() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |priority: Priority, days_ahead: i64| Action {
+            entity_id: "entity".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(days_ahead),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action(Priority::Urgent, 1),
+            action(Priority::Normal, 2),
+            action(Priority::Normal, 3),
+            action(Priority::Normal, 4),
+        ];
+
+        let config = FilterConfig {
+            per_entity_limit: Some(2),
+            dedup_strategy: DedupStrategy::HighestPriority,
+            ..Default::default()
+        };
+        let output = process_actions(input.clone(), now, &config);
+        ensure!(
+            output == [action(Priority::Urgent, 1), action(Priority::Normal, 4)],
+            "Expected the urgent and the latest normal occurrence, got {output:?}"
+        );
+
+        for strategy in [DedupStrategy::LastSeen, DedupStrategy::HighestPriority] {
+            let dedup = FilterConfig { dedup_strategy: strategy, ..Default::default() };
+            let limited = FilterConfig { per_entity_limit: Some(1), ..dedup.clone() };
+            ensure!(
+                process_actions(input.clone(), now, &limited)
+                    == process_actions(input.clone(), now, &dedup),
+                "Expected a limit of 1 to match {strategy:?} dedup"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_case_insensitive_ids_dedup_together() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action("entity_1"), action("Entity_1")];
+
+        let output = process_actions(input.clone(), now, &FilterConfig::default());
+        ensure!(output.len() == 2, "Expected case-sensitive ids by default, got {output:?}");
+
+        let config = FilterConfig { case_insensitive_ids: true, ..Default::default() };
+        let output = process_actions(input, now, &config);
+        ensure!(output.len() == 1, "Expected differently-cased ids to collapse, got {output:?}");
+        ensure!(output[0].entity_id == "Entity_1", "Expected the survivor's casing to be kept");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_by_content_hash_ignores_unhashed_fields() -> Result<()> {
+        // ---
+        let first = Action {
+            entity_id: "import_a".to_string(),
+            last_action_time: parse_date("2025-05-01T00:00:00Z")?,
+            next_action_time: parse_date("2025-07-01T00:00:00Z")?,
+            priority: Priority::Normal,
+            ..Default::default()
+        };
+        // Same content apart from entity_id, which is not part of the hash
+        let second = Action { entity_id: "import_b".to_string(), ..first.clone() };
+
+        let fields = ["last_action_time", "next_action_time", "priority"];
+        let config = FilterConfig {
+            dedup_by: DedupKey::ContentHash { fields: fields.map(String::from).to_vec() },
+            ..Default::default()
+        };
+        let input = vec![first, second];
+
+        let output = process_actions(input.clone(), reference_now()?, &config);
+        ensure!(output.len() == 1, "Expected content duplicates to collapse, got {:?}", output);
+        ensure!(output[0].entity_id == "import_b", "Expected last occurrence to win");
+
+        let output = process_actions(input, reference_now()?, &FilterConfig::default());
+        ensure!(output.len() == 2, "Expected distinct entity_ids to survive by default");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_near_duplicates_collapse_within_window() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let normal = Action {
+            entity_id: "jittery".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority: Priority::Normal,
+            ..Default::default()
+        };
+        let urgent = Action {
+            next_action_time: normal.next_action_time + Duration::seconds(3),
+            priority: Priority::Urgent,
+            ..normal.clone()
+        };
+
+        // Hash on next_action_time so both records survive the first dedup pass
+        let fields = ["entity_id", "next_action_time"].map(String::from).to_vec();
+        let config = FilterConfig {
+            dedup_by: DedupKey::ContentHash { fields },
+            near_duplicate_window_secs: Some(10),
+            ..Default::default()
+        };
+        let output = process_actions(vec![normal.clone(), urgent.clone()], now, &config);
+
+        ensure!(output.len() == 1, "Expected near duplicates to collapse, got {output:?}");
+        ensure!(output[0] == urgent, "Expected the urgent record to survive");
+
+        let config = FilterConfig { near_duplicate_window_secs: Some(2), ..config };
+        let output = process_actions(vec![normal, urgent], now, &config);
+        ensure!(output.len() == 2, "Expected records outside the window to survive");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_heap_order_pops_chronologically() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_days: i64, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("third", 30, Priority::Urgent),
+            action("first", 2, Priority::Normal),
+            action("fourth", 60, Priority::Urgent),
+            action("second", 9, Priority::Normal),
+        ];
+
+        let config = FilterConfig { sort_key: SortKey::HeapOrder, ..Default::default() };
+        let output = process_actions(input, now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+
+        ensure!(ids == ["first", "second", "third", "fourth"], "Unexpected pop order {ids:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_duration_policy() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let instant = now - Duration::days(10);
+        let input = vec![Action {
+            entity_id: "zero".to_string(),
+            last_action_time: instant,
+            next_action_time: instant,
+            ..Default::default()
+        }];
+
+        let output = process_actions(input.clone(), now, &FilterConfig::default());
+        ensure!(output.len() == 1, "Expected zero-duration action to be kept by default");
+
+        let config =
+            FilterConfig { zero_duration_policy: ZeroDurationPolicy::Drop, ..Default::default() };
+        let output = process_actions(input, now, &config);
+        ensure!(output.is_empty(), "Expected zero-duration action to be dropped");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iterators_match_vec_pipeline() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last_days: i64, next_days: i64, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now + Duration::days(last_days),
+            next_action_time: now + Duration::days(next_days),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("normal_1", -10, 5, Priority::Normal),
+            action("too_far", -10, 120, Priority::Urgent),
+            action("urgent_1", -20, 15, Priority::Urgent),
+            action("too_recent", -1, 5, Priority::Urgent),
+            action("normal_1", -30, 6, Priority::Urgent),
+            action("normal_2", -9, 40, Priority::Normal),
+        ];
+        let passthrough_configs = [
+            FilterConfig::default(),
+            FilterConfig { dedup_strategy: DedupStrategy::HighestPriority, ..Default::default() },
+            FilterConfig { dedup_strategy: DedupStrategy::EarliestNext, ..Default::default() },
+            FilterConfig { sort_key: SortKey::NextActionTimeDesc, ..Default::default() },
+            FilterConfig { top_normal: Some(1), ..Default::default() },
+            FilterConfig {
+                sample: Some(SampleConfig { fraction: 0.5, seed: 7 }),
+                ..Default::default()
+            },
+        ];
+        for config in &passthrough_configs {
+            let expected = process_actions(input.clone(), now, config);
+            let borrowed: Vec<Action> = filter_actions_iter(&input, now, config).cloned().collect();
+            ensure!(borrowed == expected, "Expected {expected:?}, got {borrowed:?} for {config:?}");
+        }
+        let config = FilterConfig::default();
+
+        // Rewriting configs yield the original records (see `filter_actions_iter`)
+        let overridden = FilterConfig {
+            priority_overrides: HashMap::from([("normal_2".to_string(), Priority::Urgent)]),
+            ..Default::default()
+        };
+        let borrowed: Vec<&Action> = filter_actions_iter(&input, now, &overridden).collect();
+        ensure!(
+            borrowed.iter().any(|a| a.entity_id == "normal_2" && a.priority == Priority::Normal),
+            "Expected the original priority, got {borrowed:?}"
+        );
+
+        // Lazy filters only: keeps both normal_1 occurrences, in input order
+        let lazy: Vec<&Action> = filter_only_iter(&input, now, &config).collect();
+        let lazy_ids: Vec<&str> = lazy.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(
+            lazy_ids == ["normal_1", "urgent_1", "normal_1", "normal_2"],
+            "Unexpected lazy survivors {lazy_ids:?}"
+        );
+        ensure!(std::ptr::eq(lazy[0], &input[0]), "Expected borrowed items from the input");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_band_caps_keep_top_of_each_priority() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input: Vec<Action> = (0..10)
+            .map(|i| Action {
+                entity_id: format!("entity_{i}"),
+                last_action_time: now - Duration::days(10),
+                next_action_time: now + Duration::days(i),
+                priority: if i % 2 == 0 { Priority::Urgent } else { Priority::Normal },
+                ..Default::default()
+            })
+            .collect();
+
+        let config =
+            FilterConfig { top_urgent: Some(2), top_normal: Some(3), ..Default::default() };
+        let (output, report) = process_actions_with_report(input, now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+
+        ensure!(
+            ids == ["entity_0", "entity_2", "entity_1", "entity_3", "entity_5"],
+            "Unexpected capped output {ids:?}"
+        );
+        ensure!(report.capped == 5, "Expected 5 capped actions, got {}", report.capped);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_log_sample_size_logs_leading_survivors() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input: Vec<Action> = ["a", "b", "c"]
+            .iter()
+            .map(|id| Action {
+                entity_id: id.to_string(),
+                last_action_time: now - Duration::days(10),
+                next_action_time: now + Duration::days(5),
+                ..Default::default()
+            })
+            .collect();
+
+        let config = FilterConfig { log_sample_size: 2, ..Default::default() };
+        let (output, logs) =
+            capture_logs(tracing::Level::INFO, || process_actions(input.clone(), now, &config));
+        ensure!(output.len() == 3, "Expected all actions kept, got {}", output.len());
+        let lines = logs.lines().filter(|l| l.contains("Sample survivor")).count();
+        ensure!(lines == 2, "Expected 2 sample lines, got {lines}: {logs}");
+
+        let (_, logs) = capture_logs(tracing::Level::INFO, || {
+            process_actions(input, now, &FilterConfig::default())
+        });
+        ensure!(!logs.contains("Sample survivor"), "Expected no sample lines by default: {logs}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_details_are_logged_when_enabled() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("a", Priority::Normal),
+            action("a", Priority::Urgent),
+            action("a", Priority::Urgent),
+            action("b", Priority::Normal),
+            action("b", Priority::Normal),
+        ];
+
+        let config = FilterConfig { log_dedup_details: true, ..Default::default() };
+        let (_, logs) =
+            capture_logs(tracing::Level::DEBUG, || process_actions(input.clone(), now, &config));
+        let lines = logs.lines().filter(|l| l.contains("dropped duplicate")).count();
+        ensure!(lines == 3, "Expected 3 dedup lines, got {lines}: {logs}");
+        ensure!(
+            logs.contains(r#"differing_fields=["priority"]"#),
+            "Expected differing fields to be named: {logs}"
+        );
+
+        let (_, logs) = capture_logs(tracing::Level::DEBUG, || {
+            process_actions(input, now, &FilterConfig::default())
+        });
+        ensure!(!logs.contains("dropped duplicate"), "Expected no dedup lines by default");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_input_policy() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+
+        let (output, _) = try_process_actions(Vec::new(), now, &FilterConfig::default())?;
+        ensure!(output.is_empty(), "Expected an empty result under Allow, got {output:?}");
+
+        let config =
+            FilterConfig { empty_input_policy: EmptyInputPolicy::Error, ..Default::default() };
+        let err = try_process_actions(Vec::new(), now, &config).err();
+        ensure!(
+            err.as_ref().is_some_and(|e| e.code() == "empty_input"),
+            "Expected an empty_input error, got {err:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entity_id_pattern_rejects_malformed_ids() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action("entity_1"), action("Entity 1"), action("entity_2")];
+
+        let config = FilterConfig::from_json(r#"{"entity_id_pattern": "^[a-z0-9_]+$"}"#)?;
+        let (output, report) = process_actions_with_report(input.clone(), now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(!ids.contains(&"Entity 1"), "Expected the malformed id dropped, got {ids:?}");
+        ensure!(ids.len() == 2, "Expected the well-formed ids kept, got {ids:?}");
+        ensure!(report.validation == 1, "Expected 1 validation drop, got {}", report.validation);
+
+        let config = FilterConfig { malformed_id_policy: MalformedIdPolicy::Error, ..config };
+        let err = try_process_actions(input, now, &config).err();
+        ensure!(
+            matches!(err, Some(FilterError::MalformedEntityId(ref indices)) if indices == &[1]),
+            "Expected index 1 reported, got {err:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deny_pattern_drops_matching_ids() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action("test_42"), action("entity_1")];
+
+        let config = FilterConfig::from_json(r#"{"deny_pattern": "test_.*"}"#)?;
+        let (output, report) = try_process_actions(input.clone(), now, &config)?;
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["entity_1"], "Unexpected survivors {ids:?}");
+        ensure!(report.denied == 1, "Expected 1 denied action, got {}", report.denied);
+
+        let err = FilterConfig::from_json(r#"{"deny_pattern": "test_("}"#).err();
+        ensure!(
+            err.as_ref().is_some_and(|e| e.to_string().contains("deny_pattern")),
+            "Expected an invalid pattern to fail config loading, got {err:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_is_reproducible_per_seed() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input: Vec<Action> = (0..200)
+            .map(|i| Action {
+                entity_id: format!("entity_{i}"),
+                last_action_time: now - Duration::days(10),
+                next_action_time: now + Duration::days(5),
+                ..Default::default()
+            })
+            .collect();
+        let sampled = |seed: u64| -> Vec<String> {
+            let config = FilterConfig {
+                sample: Some(SampleConfig { fraction: 0.5, seed }),
+                ..Default::default()
+            };
+            process_actions(input.clone(), now, &config).into_iter().map(|a| a.entity_id).collect()
+        };
+
+        let first = sampled(7);
+        ensure!(first == sampled(7), "Expected the same seed to pick the same subset");
+        ensure!(first != sampled(8), "Expected a different seed to pick a different subset");
+        ensure!((60..140).contains(&first.len()), "Expected roughly half, got {}", first.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_next_time_warning() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input: Vec<Action> = (0..12)
+            .map(|i| Action {
+                entity_id: format!("entity_{i}"),
+                last_action_time: now - Duration::days(10),
+                next_action_time: now + Duration::days(if i < 10 { 5 } else { i }),
+                ..Default::default()
+            })
+            .collect();
+
+        let config =
+            FilterConfig { shared_next_time_warn_threshold: Some(5), ..Default::default() };
+        let ((output, report), logs) = capture_logs(tracing::Level::WARN, || {
+            process_actions_with_report(input.clone(), now, &config)
+        });
+        ensure!(output.len() == 12, "Expected the check to drop nothing, got {}", output.len());
+        ensure!(report.max_shared_next_time == 10, "Unexpected {}", report.max_shared_next_time);
+        ensure!(logs.contains("count=10"), "Expected a shared-timestamp warning: {logs}");
+
+        let (_, logs) = capture_logs(tracing::Level::WARN, || {
+            process_actions(input, now, &FilterConfig::default())
+        });
+        ensure!(!logs.contains("next_action_time"), "Expected no warning by default: {logs}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadline_returns_partial_results() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input: Vec<Action> = (0..10_000)
+            .map(|i| Action {
+                entity_id: format!("entity_{i}"),
+                last_action_time: now - Duration::days(10),
+                next_action_time: now + Duration::days(5),
+                ..Default::default()
+            })
+            .collect();
+
+        let config = FilterConfig { deadline_ms: Some(0), ..Default::default() };
+        let (output, report) = process_actions_with_report(input.clone(), now, &config);
+        ensure!(report.truncated, "Expected the report to be flagged truncated");
+        ensure!(
+            !output.is_empty() && output.len() < input.len(),
+            "Expected a partial result, got {} of {}",
+            output.len(),
+            input.len()
+        );
+
+        let (output, report) = process_actions_with_report(input, now, &FilterConfig::default());
+        ensure!(!report.truncated && output.len() == 10_000, "Expected no truncation by default");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_whole_record_only_collapses_identical() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let original = Action {
+            entity_id: "entity_1".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let rescheduled = Action { next_action_time: now + Duration::days(6), ..original.clone() };
+        let input = vec![original.clone(), rescheduled, original];
+
+        let config = FilterConfig { dedup_by: DedupKey::WholeRecord, ..Default::default() };
+        let output = process_actions(input, now, &config);
+
+        ensure!(output.len() == 2, "Expected only the identical pair to collapse, got {output:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_whole_record_keeps_case_variants_apart() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, tags: &[&str]| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let input = vec![action("E1", &["a", "b"]), action("e1", &["b", "a"])];
+
+        let config = FilterConfig { dedup_by: DedupKey::WholeRecord, ..Default::default() };
+        let output = process_actions(input.clone(), now, &config);
+        ensure!(output.len() == 2, "Expected non-identical records to survive, got {output:?}");
+
+        let fields = vec!["entity_id".to_string(), "tags".to_string()];
+        let config =
+            FilterConfig { dedup_by: DedupKey::ContentHash { fields }, ..Default::default() };
+        let output = process_actions(input, now, &config);
+        ensure!(output.len() == 1, "Expected content keys to canonicalize, got {output:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_id_policy() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action("entity_1"), action(""), action("entity_2"), action("  ")];
+
+        let (output, report) = try_process_actions(input.clone(), now, &FilterConfig::default())?;
+        ensure!(output.len() == 2, "Expected empty ids to be skipped, got {output:?}");
+        ensure!(report.validation == 2, "Expected 2 validation drops, got {}", report.validation);
+
+        let config = FilterConfig { empty_id_policy: EmptyIdPolicy::Error, ..Default::default() };
+        match try_process_actions(input.clone(), now, &config) {
+            Err(FilterError::EmptyEntityId(indices)) => {
+                ensure!(indices == [1, 3], "Unexpected offending indices {indices:?}")
+            }
+            other => anyhow::bail!("Expected EmptyEntityId error, got {other:?}"),
+        }
+
+        let config = FilterConfig { validation_mode: ValidationMode::FailFast, ..config };
+        match try_process_actions(input, now, &config) {
+            Err(FilterError::EmptyEntityId(indices)) => {
+                ensure!(indices == [1], "Expected only the first offender, got {indices:?}")
+            }
+            other => anyhow::bail!("Expected EmptyEntityId error, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_entity_id_len_rejects_or_truncates() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input = vec![Action {
+            entity_id: "x".repeat(1000),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        }];
+
+        let config = FilterConfig { max_entity_id_len: Some(64), ..Default::default() };
+        let (output, report) = process_actions_with_report(input.clone(), now, &config);
+        ensure!(output.is_empty(), "Expected the long id to be rejected, got {output:?}");
+        ensure!(report.validation == 1, "Expected 1 validation drop, got {}", report.validation);
+
+        let config = FilterConfig { entity_id_len_policy: LengthPolicy::Truncate, ..config };
+        let (output, logs) =
+            capture_logs(tracing::Level::WARN, || process_actions(input, now, &config));
+        ensure!(output.len() == 1, "Expected the long id to survive, got {}", output.len());
+        ensure!(output[0].entity_id == "x".repeat(64), "Expected a 64-char id");
+        ensure!(logs.contains("original_len=1000"), "Expected truncation to be logged: {logs}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_expected_survivors_guard() -> Result<()> {
+        // ---
+        let input: Vec<Action> =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+
+        let config = FilterConfig { min_expected_survivors: Some(5), ..Default::default() };
+        match try_process_actions(input.clone(), reference_now()?, &config) {
+            Err(FilterError::TooFewSurvivors { kept: 2, expected: 5 }) => {}
+            other => anyhow::bail!("Expected TooFewSurvivors, got {other:?}"),
+        }
+
+        let config = FilterConfig { min_expected_survivors: Some(2), ..Default::default() };
+        let (output, _) = try_process_actions(input, reference_now()?, &config)?;
+        ensure!(output.len() == 2, "Expected the guard to pass at the threshold");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_actions_resorts_cached_result() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_days: i64, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("normal_soon", 1, Priority::Normal),
+            action("urgent_late", 40, Priority::Urgent),
+            action("urgent_soon", 3, Priority::Urgent),
+        ];
+        let config = FilterConfig::default();
+
+        let mut cached = process_actions(input, now, &config);
+        let ids: Vec<&str> = cached.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["urgent_late", "urgent_soon", "normal_soon"], "Unexpected {ids:?}");
+
+        sort_actions(&mut cached, SortKey::NextActionTime, &config);
+        let ids: Vec<&str> = cached.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["normal_soon", "urgent_soon", "urgent_late"], "Unexpected {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recency_boost_ranks_recent_touch_first() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last_days: i64, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(last_days),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("normal_old", 60, Priority::Normal),
+            action("normal_recent", 8, Priority::Normal),
+            action("urgent_old", 60, Priority::Urgent),
+        ];
+
+        let output = process_actions(input.clone(), now, &FilterConfig::default());
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["urgent_old", "normal_old", "normal_recent"], "Unexpected {ids:?}");
+
+        let config = FilterConfig { recency_boost: Some(0.01), ..Default::default() };
+        let output = process_actions(input, now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["urgent_old", "normal_recent", "normal_old"], "Unexpected {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_action_time_sort_ties_within_granularity() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let due = now + Duration::days(5);
+        let action = |id: &str, offset_ms: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: due + Duration::milliseconds(offset_ms),
+            ..Default::default()
+        };
+        let input = vec![action("b_earlier", 0), action("a_later", 200)];
+
+        let config = FilterConfig {
+            sort_key: SortKey::NextActionTime,
+            comparison_granularity: Granularity::Minute,
+            ..Default::default()
+        };
+        let output = process_actions(input.clone(), now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["a_later", "b_earlier"], "Expected an entity_id tie-break, got {ids:?}");
+
+        let mut exact = input;
+        sort_actions(
+            &mut exact,
+            SortKey::NextActionTime,
+            &FilterConfig { comparison_granularity: Granularity::Second, ..config },
+        );
+        let ids: Vec<&str> = exact.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["a_later", "b_earlier"], "Expected a tie within one second, got {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_score_sort_puts_highest_first() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, score: Option<f64>, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            score,
+            ..Default::default()
+        };
+        let input = vec![
+            action("unscored", None, Priority::Urgent),
+            action("mid_normal", Some(0.5), Priority::Normal),
+            action("high", Some(0.9), Priority::Normal),
+            action("mid_urgent", Some(0.5), Priority::Urgent),
+            action("low", Some(0.1), Priority::Urgent),
+        ];
+
+        let config = FilterConfig { sort_key: SortKey::Score, ..Default::default() };
+        let output = process_actions(input, now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(
+            ids == ["high", "mid_urgent", "mid_normal", "low", "unscored"],
+            "Unexpected score order {ids:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hashed_tie_break_is_reproducible() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input: Vec<Action> = (0..10)
+            .map(|i| Action {
+                entity_id: format!("entity_{i}"),
+                last_action_time: now - Duration::days(10),
+                next_action_time: now + Duration::days(5),
+                ..Default::default()
+            })
+            .collect();
+        let order = |tie_break: TieBreak| -> Vec<String> {
+            let config = FilterConfig { tie_break, ..Default::default() };
+            process_actions(input.clone(), now, &config).into_iter().map(|a| a.entity_id).collect()
+        };
+
+        let input_ids: Vec<String> = input.iter().map(|a| a.entity_id.clone()).collect();
+        ensure!(order(TieBreak::InputOrder) == input_ids, "Expected input order by default");
+
+        let seeded = order(TieBreak::Hashed { seed: 1 });
+        ensure!(seeded == order(TieBreak::Hashed { seed: 1 }), "Expected a reproducible order");
+        ensure!(seeded != input_ids, "Expected the hashed order to differ from input order");
+        ensure!(seeded != order(TieBreak::Hashed { seed: 2 }), "Expected seeds to differ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_overrides_are_audited() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let input =
+            vec![action("entity_4", Priority::Normal), action("entity_5", Priority::Normal)];
+
+        let config = FilterConfig {
+            priority_overrides: HashMap::from([("entity_5".to_string(), Priority::Urgent)]),
+            ..Default::default()
+        };
+        let (output, report) = process_actions_with_report(input, now, &config);
+        ensure!(output[0].entity_id == "entity_5", "Expected the override to sort first");
+        ensure!(output[0].priority == Priority::Urgent, "Expected entity_5 to become Urgent");
+        ensure!(
+            report.override_audit
+                == [PriorityOverride {
+                    entity_id: "entity_5".to_string(),
+                    from: Priority::Normal,
+                    to: Priority::Urgent,
+                }],
+            "Unexpected audit {:?}",
+            report.override_audit
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_below_rewrites_lower_tiers() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![action("normal", Priority::Normal), action("urgent", Priority::Urgent)];
+        let priorities = |collapse_below: Option<Priority>| -> Vec<Priority> {
+            let config = FilterConfig { collapse_below, ..Default::default() };
+            process_actions(input.clone(), now, &config).into_iter().map(|a| a.priority).collect()
+        };
+
+        // Normal is the lowest tier, so nothing lies below it
+        let unchanged = priorities(Some(Priority::Normal));
+        ensure!(unchanged == [Priority::Urgent, Priority::Normal], "Unexpected {unchanged:?}");
+        let collapsed = priorities(Some(Priority::Urgent));
+        ensure!(collapsed == [Priority::Urgent, Priority::Urgent], "Unexpected {collapsed:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_action_desc_tie_break() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last_days: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(last_days),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let input = vec![action("older", 20), action("newer", 10)];
+        let order = |tie_break: TieBreak| -> Vec<String> {
+            let config = FilterConfig { tie_break, ..Default::default() };
+            process_actions(input.clone(), now, &config).into_iter().map(|a| a.entity_id).collect()
+        };
+
+        ensure!(order(TieBreak::InputOrder) == ["older", "newer"], "Expected input order");
+        let recent_first = order(TieBreak::LastActionDesc);
+        ensure!(recent_first == ["newer", "older"], "Unexpected order {recent_first:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_order_inverts_sort() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("urgent_1", Priority::Urgent),
+            action("normal_1", Priority::Normal),
+            action("urgent_2", Priority::Urgent),
+            action("normal_2", Priority::Normal),
+        ];
+
+        let config: FilterConfig =
+            serde_json::from_str(r#"{"priority_order": ["normal", "urgent"]}"#)?;
+        let output = process_actions(input.clone(), now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["normal_1", "normal_2", "urgent_1", "urgent_2"], "Got {ids:?}");
+
+        let output = process_actions(input, now, &FilterConfig::default());
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["urgent_1", "urgent_2", "normal_1", "normal_2"], "Got {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_order_governs_duplicate_resolution() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |priority: Priority, next_days: i64| Action {
+            entity_id: "duplicate".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            priority,
+            ..Default::default()
+        };
+        let inverted = |dedup_strategy| FilterConfig {
+            dedup_strategy,
+            priority_order: vec![Priority::Normal, Priority::Urgent],
+            ..Default::default()
+        };
+        let priorities = |output: Vec<Action>| -> Vec<Priority> {
+            output.into_iter().map(|a| a.priority).collect()
+        };
+
+        // HighestPriority keeps the occurrence first in the configured order, either way round
+        for input in [
+            vec![action(Priority::Urgent, 5), action(Priority::Normal, 5)],
+            vec![action(Priority::Normal, 5), action(Priority::Urgent, 5)],
+        ] {
+            let output = process_actions(input, now, &inverted(DedupStrategy::HighestPriority));
+            ensure!(priorities(output) == [Priority::Normal], "Expected Normal to win");
+        }
+
+        let input = vec![action(Priority::Normal, 5), action(Priority::Urgent, 5)];
+        let output = process_actions(input, now, &inverted(DedupStrategy::SmartMerge));
+        ensure!(priorities(output) == [Priority::Normal], "Expected SmartMerge to keep Normal");
+
+        // Near-duplicates left by record-level dedup collapse the same way
+        let input = vec![action(Priority::Urgent, 5), action(Priority::Normal, 6)];
+        let config = FilterConfig {
+            dedup_by: DedupKey::WholeRecord,
+            near_duplicate_window_secs: Some(2 * 24 * 60 * 60),
+            ..inverted(DedupStrategy::LastSeen)
+        };
+        let output = process_actions(input, now, &config);
+        ensure!(priorities(output) == [Priority::Normal], "Expected the Normal near-duplicate");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_action_time_desc_puts_latest_first() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_days: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            ..Default::default()
+        };
+        let input = vec![action("middle", 20), action("latest", 60), action("earliest", 2)];
+        let config = FilterConfig { sort_key: SortKey::NextActionTimeDesc, ..Default::default() };
+
+        let output = process_actions(input, now, &config);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["latest", "middle", "earliest"], "Unexpected {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_urgent_overdue_needs_both() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_days: i64, priority: Priority| Action {
+            entity_id: id.to_string(),
+            next_action_time: now + Duration::days(next_days),
+            priority,
+            ..Default::default()
+        };
+        let actions = vec![
+            action("urgent_overdue", -2, Priority::Urgent),
+            action("urgent_upcoming", 2, Priority::Urgent),
+            action("normal_overdue", -2, Priority::Normal),
+            action("urgent_overdue_2", -9, Priority::Urgent),
+        ];
+
+        let output = urgent_overdue(actions, now);
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["urgent_overdue", "urgent_overdue_2"], "Unexpected {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remapped_ids_dedup_together() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_days: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(next_days),
+            ..Default::default()
+        };
+        let mut input = vec![action("old_a", 5), action("old_b", 6), action("other", 7)];
+        let mapping = HashMap::from([
+            ("old_a".to_string(), "new".to_string()),
+            ("old_b".to_string(), "new".to_string()),
+        ]);
+
+        remap_entity_ids(&mut input, &mapping);
+        let output = process_actions(input, now, &FilterConfig::default());
+        let ids: Vec<&str> = output.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["new", "other"], "Expected the remapped ids to collapse, got {ids:?}");
+        ensure!(output[0] == action("new", 6), "Expected the last occurrence to win");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_actions_after_pages_in_sort_order() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, priority: Priority| Action {
+            entity_id: id.to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now + Duration::days(5),
+            priority,
+            ..Default::default()
+        };
+        let input = vec![
+            action("n1", Priority::Normal),
+            action("u1", Priority::Urgent),
+            action("n2", Priority::Normal),
+            action("u2", Priority::Urgent),
+        ];
+        let sorted = process_actions(input, now, &FilterConfig::default());
+        let ids = |actions: &[Action]| -> Vec<String> {
+            actions.iter().map(|a| a.entity_id.clone()).collect()
+        };
+        ensure!(ids(&sorted) == ["u1", "u2", "n1", "n2"], "Unexpected order {:?}", ids(&sorted));
+
+        let page = actions_after(sorted.clone(), "u2");
+        ensure!(ids(&page) == ["n1", "n2"], "Unexpected page {:?}", ids(&page));
+        ensure!(actions_after(sorted.clone(), "n2").is_empty(), "Expected nothing after the last");
+        ensure!(actions_after(sorted, "missing").is_empty(), "Expected nothing for an unknown id");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_due_bucket() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_days: i64| Action {
+            entity_id: id.to_string(),
+            next_action_time: now + Duration::days(next_days),
+            ..Default::default()
+        };
+        let actions = vec![action("week", 3), action("month", 20), action("later", 80)];
+
+        let buckets = group_by_due_bucket(actions, now);
+        let ids = |bucket: DueBucket| -> Vec<&str> {
+            buckets.get(&bucket).into_iter().flatten().map(|a| a.entity_id.as_str()).collect()
+        };
+        ensure!(ids(DueBucket::ThisWeek) == ["week"], "Unexpected {buckets:?}");
+        ensure!(ids(DueBucket::ThisMonth) == ["month"], "Unexpected {buckets:?}");
+        ensure!(ids(DueBucket::Later) == ["later"], "Unexpected {buckets:?}");
+        ensure!(!buckets.contains_key(&DueBucket::Overdue), "Expected no overdue bucket");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_due_histogram_weekly_buckets() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let actions: Vec<Action> = [3, 5, 20]
+            .iter()
+            .map(|days| Action {
+                next_action_time: now + Duration::days(*days),
+                ..Default::default()
+            })
+            .collect();
+
+        let histogram = due_histogram(&actions, now, 7);
+        ensure!(histogram == [(0, 2), (14, 1)], "Unexpected histogram {histogram:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_wakeup_skips_past_actions() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next_hours: i64| Action {
+            entity_id: id.to_string(),
+            next_action_time: now + Duration::hours(next_hours),
+            ..Default::default()
+        };
+        let actions = vec![
+            action("later", 48),
+            action("overdue", -3),
+            action("soonest", 2),
+            action("due", 0),
+        ];
+
+        let wakeup = next_wakeup(&actions, now);
+        ensure!(wakeup == Some(now + Duration::hours(2)), "Unexpected wakeup {wakeup:?}");
+        ensure!(next_wakeup(&actions[1..2], now).is_none(), "Expected None when all are due");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_hook_sees_survivors() -> Result<()> {
+        // ---
+        let input: Vec<Action> =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+
+        let output = process_actions_then(
+            input,
+            reference_now()?,
+            &FilterConfig::default(),
+            |mut actions| {
+                actions.iter_mut().for_each(|a| a.tags.push("enriched".to_string()));
+                actions
+            },
+        );
+
+        ensure!(output.len() == 2, "Expected the two sample survivors, got {}", output.len());
+        ensure!(
+            output.iter().all(|a| a.tags.last().is_some_and(|tag| tag == "enriched")),
+            "Expected every survivor to carry the appended tag: {output:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_future_skew_beyond_limit() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = Action {
+            entity_id: "skewed".to_string(),
+            last_action_time: now + Duration::minutes(10),
+            next_action_time: now + Duration::days(1),
+            ..Default::default()
+        };
+
+        let reject = FilterConfig { max_future_skew_minutes: Some(5), ..Default::default() };
+        ensure!(
+            apply_future_skew(action.clone(), now, &reject).is_none(),
+            "Expected 10 minutes of skew to be rejected with a 5 minute limit"
+        );
+
+        let clamp = FilterConfig { future_skew_policy: SkewPolicy::Clamp, ..reject.clone() };
+        let clamped = apply_future_skew(action.clone(), now, &clamp);
+        ensure!(
+            clamped.is_some_and(|a| a.last_action_time == now),
+            "Expected last_action_time to be clamped to now"
+        );
+
+        let lenient = FilterConfig { max_future_skew_minutes: Some(15), ..reject };
+        ensure!(
+            apply_future_skew(action, now, &lenient).is_some(),
+            "Expected 10 minutes of skew to be accepted with a 15 minute limit"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_reason_summary_is_logged() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last_days: i64, next_days: i64| Action {
+            entity_id: id.to_string(),
+            last_action_time: now + Duration::days(last_days),
+            next_action_time: now + Duration::days(next_days),
+            ..Default::default()
+        };
+        let input = vec![
+            action("future_1", -10, 120),
+            action("future_2", -10, 95),
+            action("stale_1", -2, 10),
+            action("skewed", 1, 10),
+            action("dup", -10, 10),
+            action("dup", -12, 12),
+            action("kept", -10, 10),
+        ];
+
+        let config = FilterConfig { max_future_skew_minutes: Some(5), ..Default::default() };
+        let ((output, report), logs) =
+            capture_logs(tracing::Level::INFO, || process_actions_with_report(input, now, &config));
+
+        ensure!(output.len() == 2, "Expected 2 survivors, got {}", output.len());
+        ensure!(
+            report
+                == FilterReport {
+                    input: 7,
+                    kept: 2,
+                    future: 2,
+                    stale: 1,
+                    duplicate: 1,
+                    validation: 1,
+                    denied: 0,
+                    low_score: 0,
+                    sampled_out: 0,
+                    capped: 0,
+                    truncated: false,
+                    max_shared_next_time: 0,
+                    dedup_audit: vec![DedupAuditEntry {
+                        entity_id: "dup".to_string(),
+                        kept_index: 5,
+                        dropped_indices: vec![4],
+                    }],
+                    override_audit: vec![],
+                },
+            "Unexpected report {report:?}"
+        );
+        ensure!(
+            logs.contains("dropped: future=2 stale=1 duplicate=1 validation=1"),
+            "Expected summary line in logs, got: {logs}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_due_sample_input() -> Result<()> {
+        // ---
+        let input: Vec<Action> =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = parse_date("2025-07-05T00:00:00Z")?;
+
+        let (due, upcoming) = partition_due(input, now, &FilterConfig::default());
+
+        let due_ids: Vec<&str> = due.iter().map(|a| a.entity_id.as_str()).collect();
+        let upcoming_ids: Vec<&str> = upcoming.iter().map(|a| a.entity_id.as_str()).collect();
+
+        // entity_1 keeps its last occurrence (due 2025-07-01), entity_2 is beyond 90 days
+        ensure!(due_ids == ["entity_1"], "Unexpected due bucket {due_ids:?}");
+        ensure!(upcoming_ids == ["entity_3"], "Unexpected upcoming bucket {upcoming_ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_action_time_exactly_now() -> Result<()> {
+        // ---
+        let now = parse_date("2025-06-28T12:34:56.789Z")?;
+        let action = Action {
+            entity_id: "at_now".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now,
+            ..Default::default()
+        };
+
+        let (due, upcoming) = partition_due(vec![action.clone()], now, &FilterConfig::default());
+        ensure!(due == [action.clone()], "Expected an action at now to be included as due");
+        ensure!(upcoming.is_empty(), "Expected nothing upcoming, got {upcoming:?}");
+
+        let config = FilterConfig { next_at_now: AtNowPolicy::Upcoming, ..Default::default() };
+        let (due, upcoming) = partition_due(vec![action.clone()], now, &config);
+        ensure!(due.is_empty(), "Expected nothing due under Upcoming, got {due:?}");
+        ensure!(upcoming == [action], "Expected the action at now to be upcoming");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_action_time_exactly_7_days() -> Result<()> {
+        // ---
+        let today = reference_now()?.date_naive();
+        let input = vec![Action {
+            entity_id: "test".into(),
+            last_action_time: DateTime::<Utc>::from_naive_utc_and_offset(
+                (today - Duration::days(DEFAULT_PAST_WINDOW_DAYS)).and_hms_opt(0, 0, 0).unwrap(),
+                Utc,
+            ),
+            next_action_time: DateTime::<Utc>::from_naive_utc_and_offset(
+                (today + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap(),
+                Utc,
+            ),
+            priority: Priority::Normal,
+            ..Default::default()
+        }];
+
+        let output = process_actions(input, reference_now()?, &FilterConfig::default());
+
+        // We expect it to be filtered out since it's exactly 7 days ago (not < 7 days)
+        ensure!(output.is_empty(), "Expected action exactly 7 days old to be excluded");
+        Ok(())
+    }
+
+    #[test]
+    fn test_clamped_record_can_pass_with_wide_epsilon() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let skewed = Action {
+            entity_id: "skewed".to_string(),
+            last_action_time: now + Duration::days(1),
+            next_action_time: now + Duration::days(5),
+            ..Default::default()
+        };
+        let config = FilterConfig {
+            max_future_skew_minutes: Some(1),
+            future_skew_policy: SkewPolicy::Clamp,
+            // Wider than the past window, so `now` itself clears the staleness boundary
+            epsilon_seconds: (DEFAULT_PAST_WINDOW_DAYS + 1) * 24 * 60 * 60,
+            ..Default::default()
+        };
+        let input = vec![skewed];
+
+        let output = process_actions(input.clone(), now, &config);
+        ensure!(output.len() == 1, "Expected the clamped record to pass, got {output:?}");
+        let lazy = filter_only_iter(&input, now, &config).count();
+        let borrowed = filter_actions_iter(&input, now, &config).count();
+        ensure!(lazy == 1 && borrowed == 1, "Expected the iterators to agree: {lazy}, {borrowed}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_epsilon_tolerates_jitter_at_boundaries() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let input = vec![Action {
+            entity_id: "jittered".to_string(),
+            last_action_time: now - Duration::days(10),
+            next_action_time: now
+                + Duration::days(DEFAULT_FUTURE_WINDOW_DAYS)
+                + Duration::seconds(1),
+            ..Default::default()
+        }];
+        let config =
+            FilterConfig { comparison_granularity: Granularity::Second, ..Default::default() };
+
+        let output = process_actions(input.clone(), now, &config);
+        ensure!(output.is_empty(), "Expected 1 second past 90 days to be dropped exactly");
+
+        let config = FilterConfig { epsilon_seconds: 5, ..config };
+        let output = process_actions(input, now, &config);
+        ensure!(output.len() == 1, "Expected a 5-second epsilon to keep the action");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_action_time_exactly_90_days() -> Result<()> {
+        // ---
+        let today = reference_now()?;
+        let input = vec![Action {
+            entity_id: "edge_90_days".to_string(),
+            last_action_time: today - Duration::days(10),
+            next_action_time: today + Duration::days(DEFAULT_FUTURE_WINDOW_DAYS),
+            priority: Priority::Normal,
+            ..Default::default()
+        }];
+
+        let output = process_actions(input, today, &FilterConfig::default());
+        ensure!(output.len() == 1, "Action 90 days out should be included");
+        Ok(())
+    }
+}
@@ -0,0 +1,1726 @@
+use crate::config::{
+    BatchErrorPolicy, DedupStrategy, FilterConfig, MapKeyPolicy, MissingLastTimePolicy,
+    NextTimeFallback, ValidationMode,
+};
+use crate::diff::diff_results;
+use crate::domain::{Action, DueBucket, Priority};
+use crate::error::FilterError;
+use crate::filter::{
+    actions_after, dedup_key, due_histogram, explain_entity, group_by_due_bucket,
+    try_process_actions, urgent_overdue,
+};
+use crate::hashing::StableHasher;
+use crate::report::FilterReport;
+use crate::timestamp::{normalize_timestamps, parse_timestamp, utc};
+use base64::prelude::*;
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::sync::Mutex;
+
+/// Schema version reported when `response_version` is enabled; bump on breaking changes
+pub const RESPONSE_SCHEMA_VERSION: u32 = 1;
+
+/// Object form of a request: the actions (or several batches of them) plus per-request
+/// options. A bare array (or entity_id map) is treated as an envelope with default options.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Envelope {
+    /// Array of actions or map of entity_id -> action
+    actions: Value,
+    /// Independent sub-batches, each an array or map like `actions`, filtered separately;
+    /// the response is `{"batches": [<body or problem>, ...]}` in the same order
+    /// (see `batch_error_policy` for the alternatives)
+    batches: Option<Vec<Value>>,
+    /// Return only the entity_ids, in sort order
+    ids_only: bool,
+    /// Return an object keyed by due bucket (`overdue`, `this_week`, ...) instead of a list
+    group_by_due: bool,
+    /// entity_ids the caller already holds; the response becomes
+    /// `{"added": [...], "removed": [...], "still_present": [...]}`
+    previous_ids: Option<Vec<String>>,
+    /// Return only Urgent actions whose next_action_time has passed
+    urgent_overdue_only: bool,
+    /// IANA zone (e.g. "America/New_York"); each action also gets
+    /// `last_action_time_local` and `next_action_time_local` strings in it
+    local_tz: Option<Tz>,
+    /// Pretty-print the serialized response (see `handle_request_text`)
+    pretty: bool,
+    /// Wrap the result as `{"actions": [...], "dedup_audit": [...]}`, naming the kept and
+    /// dropped input indices of every deduplicated entity
+    dedup_audit: bool,
+    /// Wrap the result as `{"actions": [...], "override_audit": [...]}`, listing the
+    /// `{entity_id, from, to}` of every priority changed by `priority_overrides`
+    include_override_audit: bool,
+    /// Return only the counts, `{"input": N, "kept": K, "dropped": {<reason>: n, ...}}`
+    stats_only: bool,
+    /// Add an integer `days_until_due` to each action (negative once overdue)
+    annotate_days_until_due: bool,
+    /// Serialize each action's timestamps as integer seconds relative to `now`
+    /// (negative for the past) instead of RFC3339 strings
+    relative_times: bool,
+    /// Return `{"chunks": [[...], ...]}` with at most this many actions per chunk,
+    /// in overall sort order
+    chunk_size: Option<NonZeroUsize>,
+    /// Return `{"histogram": [{"offset_days": d, "count": n}, ...]}`, counting survivors
+    /// per bucket of this many days until due
+    due_histogram_days: Option<NonZeroU32>,
+    /// Cursor: return only the survivors after this entity_id in sort order
+    after_entity_id: Option<String>,
+    /// Return at most this many survivors (typically with `after_entity_id`)
+    page_size: Option<NonZeroUsize>,
+    /// Add an `action_id` to each action: a stable hash of its entity_id and
+    /// next_action_time, for idempotent downstream upserts
+    annotate_action_id: bool,
+    /// Add an integer `occurrence_count` to each action: how many input records shared
+    /// its dedup key (1 when it had no duplicates)
+    occurrence_count: bool,
+    /// Serialize each action's fields in this order (e.g. `["priority", "entity_id"]`);
+    /// fields not named keep their usual order after the listed ones
+    field_order: Option<Vec<String>>,
+    /// `Accept-Encoding` of the caller; when it lists gzip, responses of at least
+    /// `gzip_min_bytes` come back gzipped (see `handle_request_text`)
+    accept_encoding: Option<String>,
+    /// Dedup strategy for this request only, overriding the config's `dedup_strategy`
+    dedup_strategy: Option<DedupStrategy>,
+    /// Return `{"entity_id": ..., "occurrences": [{"index", "outcome", ...}]}` tracing
+    /// why each occurrence of this entity_id was kept or dropped, instead of the actions
+    explain_entity: Option<String>,
+}
+
+impl Envelope {
+    /// Splits a payload into its envelope, wrapping bare arrays and maps.
+    /// Any object with an `actions` or `batches` key is an envelope, even when every value
+    /// is an object (e.g. a map-shaped `actions` and no other options).
+    fn from_payload(payload: Value) -> Result<Envelope, FilterError> {
+        // ---
+        match payload {
+            Value::Object(ref fields)
+                if fields.contains_key("actions") || fields.contains_key("batches") =>
+            {
+                serde_json::from_value(payload).map_err(FilterError::InvalidPayload)
+            }
+            actions => Ok(Envelope { actions, ..Default::default() }),
+        }
+    }
+
+    /// The options that shape each list, for bodies assembled from several lists
+    fn list_options(&self) -> Envelope {
+        // ---
+        Envelope {
+            ids_only: self.ids_only,
+            local_tz: self.local_tz,
+            annotate_days_until_due: self.annotate_days_until_due,
+            relative_times: self.relative_times,
+            annotate_action_id: self.annotate_action_id,
+            occurrence_count: self.occurrence_count,
+            field_order: self.field_order.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Deserializes a request payload, filters it and builds the response body.
+/// The payload may be an array of actions, a map of entity_id -> action, or an
+/// envelope `{"actions": ..., <options>}` / `{"batches": [...], <options>}`.
+/// `{"action": "describe_config"}` returns the effective config instead.
+/// Payloads over `max_payload_bytes` are rejected before any processing.
+pub fn handle_request(
+    payload: Value,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Result<Value, FilterError> {
+    // ---
+    if let Some(limit) = config.max_payload_bytes {
+        let size = serialized_len(&payload)?;
+        if size > limit {
+            return Err(FilterError::PayloadTooLarge { size, limit });
+        }
+    }
+    if let Some(name) = payload.get("action").and_then(Value::as_str) {
+        return match name {
+            "describe_config" => Ok(describe_config(config)),
+            other => Err(FilterError::UnsupportedAction(other.to_string())),
+        };
+    }
+
+    let mut envelope = Envelope::from_payload(payload)?;
+    let overridden;
+    let config = match envelope.dedup_strategy {
+        Some(dedup_strategy) => {
+            overridden = FilterConfig { dedup_strategy, ..config.clone() };
+            &overridden
+        }
+        None => config,
+    };
+    if let Some(entity_id) = envelope.explain_entity.take() {
+        let input = decode_actions(std::mem::take(&mut envelope.actions), config)?;
+        let occurrences = explain_entity(input, &entity_id, now, config);
+        return Ok(json!({ "entity_id": entity_id, "occurrences": occurrences }));
+    }
+    if let Some(batches) = envelope.batches.take() {
+        let results = process_batches(batches, now, config, &envelope);
+        let mut body = batches_body(results, config.batch_error_policy)?;
+        if config.response_version {
+            body["schema_version"] = json!(RESPONSE_SCHEMA_VERSION);
+        }
+        return Ok(body);
+    }
+
+    let (body, report) =
+        process_batch(std::mem::take(&mut envelope.actions), now, config, &envelope)?;
+    Ok(annotate(body, &report, &envelope, config.response_version))
+}
+
+/// Wraps a batch body as `{"actions": ...}` when it needs companion fields:
+/// the schema version, the dedup or override audit, or the truncation flag
+fn annotate(mut body: Value, report: &FilterReport, envelope: &Envelope, versioned: bool) -> Value {
+    // ---
+    if envelope.stats_only {
+        // The histogram is already an object; partial counts are flagged in place
+        if report.truncated {
+            body["truncated"] = json!(true);
+        }
+        return body;
+    }
+    if !report.truncated && !envelope.dedup_audit && !envelope.include_override_audit && !versioned
+    {
+        return body;
+    }
+
+    let mut wrapped = json!({ "actions": body });
+    if versioned {
+        wrapped["schema_version"] = json!(RESPONSE_SCHEMA_VERSION);
+    }
+    if envelope.dedup_audit {
+        wrapped["dedup_audit"] = json!(report.dedup_audit);
+    }
+    if envelope.include_override_audit {
+        wrapped["override_audit"] = json!(report.override_audit);
+    }
+    if report.truncated {
+        // Partial results are wrapped so callers cannot mistake them for a full answer
+        wrapped["truncated"] = json!(true);
+    }
+    wrapped
+}
+
+/// Same as `handle_request`, serializing the body to text: compact by default,
+/// pretty-printed when the envelope sets `"pretty": true`.
+/// When the envelope's `accept_encoding` lists gzip and the text is at least
+/// `gzip_min_bytes` long, it is returned as a proxy-style response instead:
+/// `{"statusCode": 200, "headers": {"Content-Encoding": "gzip", ...},
+/// "isBase64Encoded": true, "body": <base64 of the gzipped text>}`.
+pub fn handle_request_text(
+    payload: Value,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+) -> Result<String, FilterError> {
+    // ---
+    let pretty = payload.get("pretty").and_then(Value::as_bool).unwrap_or(false);
+    let gzip = payload.get("accept_encoding").and_then(Value::as_str).is_some_and(accepts_gzip);
+    let body = handle_request(payload, now, config)?;
+    let text =
+        if pretty { serde_json::to_string_pretty(&body) } else { serde_json::to_string(&body) };
+    let text = text.map_err(FilterError::InvalidPayload)?;
+    if !gzip || text.len() < config.gzip_min_bytes() {
+        return Ok(text);
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(text.as_bytes()).map_err(FilterError::Compression)?;
+    let compressed = encoder.finish().map_err(FilterError::Compression)?;
+    let response = json!({
+        "statusCode": 200,
+        "headers": { "Content-Type": "application/json", "Content-Encoding": "gzip" },
+        "isBase64Encoded": true,
+        "body": BASE64_STANDARD.encode(compressed),
+    });
+    Ok(response.to_string())
+}
+
+/// True when an `Accept-Encoding` value lists gzip (ignoring case and quality values)
+fn accepts_gzip(accept_encoding: &str) -> bool {
+    // ---
+    accept_encoding.split(',').any(|coding| {
+        let (name, params) = coding.split_once(';').unwrap_or((coding, ""));
+        let quality = params.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok());
+        name.trim().eq_ignore_ascii_case("gzip") && quality.is_none_or(|q| q > 0.0)
+    })
+}
+
+/// Length of `value` as compact JSON, counted without buffering it
+fn serialized_len(value: &Value) -> Result<usize, FilterError> {
+    // ---
+    struct ByteCounter(usize);
+
+    impl std::io::Write for ByteCounter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = ByteCounter(0);
+    serde_json::to_writer(&mut counter, value).map_err(FilterError::InvalidPayload)?;
+    Ok(counter.0)
+}
+
+/// Decodes, filters and renders one batch of actions, each phase in its own span.
+/// Also returns the filter report (deadline truncation, dedup audit).
+fn process_batch(
+    actions: Value,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+    envelope: &Envelope,
+) -> Result<(Value, FilterReport), FilterError> {
+    // ---
+    let input = tracing::info_span!("decode").in_scope(|| decode_actions(actions, config))?;
+    let occurrences = envelope.occurrence_count.then(|| Occurrences::count(&input, config));
+
+    tracing::info!("Processing event with {} actions", input.len());
+
+    let (mut actions, report) =
+        tracing::info_span!("filter").in_scope(|| try_process_actions(input, now, config))?;
+    if envelope.urgent_overdue_only {
+        actions = urgent_overdue(actions, now);
+    }
+    if let Some(after) = &envelope.after_entity_id {
+        actions = actions_after(actions, after);
+    }
+    if let Some(size) = envelope.page_size {
+        actions.truncate(size.get());
+    }
+
+    tracing::info!("Returning {} filtered actions", actions.len());
+
+    let body = tracing::info_span!("render").in_scope(|| {
+        if envelope.stats_only {
+            stats(&report)
+        } else {
+            render(actions, now, envelope, occurrences.as_ref())
+        }
+    });
+    Ok((body, report))
+}
+
+/// Processes each sub-batch independently, returning one body (or error) per batch in
+/// input order. A pool of `config.batch_concurrency` scoped worker threads (capped at the
+/// available CPUs) takes batches from a shared queue, so a slow batch does not hold back
+/// the others.
+///
+/// OS threads rather than tokio tasks: filtering is CPU-bound with no await points, so
+/// tasks would only add a runtime dependency to the library while still needing
+/// `spawn_blocking` to stay off the executor. Scoped threads also borrow `config` and
+/// `envelope` directly. Async callers should run this off their executor (the Lambda
+/// entry point uses `spawn_blocking`).
+fn process_batches(
+    batches: Vec<Value>,
+    now: DateTime<Utc>,
+    config: &FilterConfig,
+    envelope: &Envelope,
+) -> Vec<Result<Value, FilterError>> {
+    // ---
+    let run = |batch: Value| {
+        process_batch(batch, now, config, envelope)
+            .map(|(body, report)| annotate(body, &report, envelope, false))
+    };
+
+    // More workers than cores would only time-slice the same CPU-bound work
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let workers = config.batch_concurrency.unwrap_or(1).min(cpus).clamp(1, batches.len().max(1));
+    if workers == 1 {
+        return batches.into_iter().map(run).collect();
+    }
+
+    let queue = Mutex::new(batches.into_iter().enumerate());
+    let mut done: Vec<(usize, Result<Value, FilterError>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                        let Some((position, batch)) = next else {
+                            return finished;
+                        };
+                        finished.push((position, run(batch)));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    done.sort_unstable_by_key(|(position, _)| *position);
+    done.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Assembles the response to a `batches` request from the per-batch results per `policy`
+fn batches_body(
+    results: Vec<Result<Value, FilterError>>,
+    policy: BatchErrorPolicy,
+) -> Result<Value, FilterError> {
+    // ---
+    match policy {
+        BatchErrorPolicy::Inline => {
+            let batches: Vec<Value> = results
+                .into_iter()
+                .map(|result| result.unwrap_or_else(|err| err.to_problem_json()))
+                .collect();
+            Ok(json!({ "batches": batches }))
+        }
+        BatchErrorPolicy::FailAll => {
+            let batches = results.into_iter().collect::<Result<Vec<Value>, FilterError>>()?;
+            Ok(json!({ "batches": batches }))
+        }
+        BatchErrorPolicy::SkipBatch => {
+            let mut batches = Vec::with_capacity(results.len());
+            for (index, result) in results.into_iter().enumerate() {
+                match result {
+                    Ok(body) => batches.push(body),
+                    Err(err) => tracing::warn!(index, "Skipping failed batch: {err}"),
+                }
+            }
+            Ok(json!({ "batches": batches }))
+        }
+        BatchErrorPolicy::ReportPerBatch => {
+            let (results, errors): (Vec<Value>, Vec<Value>) = results
+                .into_iter()
+                .map(|result| match result {
+                    Ok(body) => (body, Value::Null),
+                    Err(err) => (Value::Null, err.to_problem_json()),
+                })
+                .unzip();
+            Ok(json!({ "results": results, "errors": errors }))
+        }
+    }
+}
+
+/// Builds the response body for the surviving actions
+fn render(
+    actions: Vec<Action>,
+    now: DateTime<Utc>,
+    envelope: &Envelope,
+    occurrences: Option<&Occurrences>,
+) -> Value {
+    // ---
+    if let Some(previous_ids) = &envelope.previous_ids {
+        let flat = envelope.list_options();
+        let diff = diff_results(previous_ids, actions);
+        return json!({
+            "added": render(diff.added, now, &flat, occurrences),
+            "removed": diff.removed,
+            "still_present": render(diff.still_present, now, &flat, occurrences),
+        });
+    }
+    if let Some(days) = envelope.due_histogram_days {
+        let histogram: Vec<Value> = due_histogram(&actions, now, i64::from(days.get()))
+            .into_iter()
+            .map(|(offset_days, count)| json!({ "offset_days": offset_days, "count": count }))
+            .collect();
+        return json!({ "histogram": histogram });
+    }
+    if envelope.group_by_due {
+        let flat = envelope.list_options();
+        let buckets: BTreeMap<DueBucket, Value> = group_by_due_bucket(actions, now)
+            .into_iter()
+            .map(|(bucket, actions)| (bucket, render(actions, now, &flat, occurrences)))
+            .collect();
+        return json!(buckets);
+    }
+    if let Some(size) = envelope.chunk_size {
+        let flat = envelope.list_options();
+        let mut chunks = Vec::with_capacity(actions.len().div_ceil(size.get()));
+        let mut rest = actions.into_iter().peekable();
+        while rest.peek().is_some() {
+            chunks.push(render(rest.by_ref().take(size.get()).collect(), now, &flat, occurrences));
+        }
+        return json!({ "chunks": chunks });
+    }
+    if envelope.ids_only {
+        let ids: Vec<String> = actions.into_iter().map(|a| a.entity_id).collect();
+        return json!(ids);
+    }
+    if envelope.local_tz.is_some()
+        || envelope.annotate_days_until_due
+        || envelope.relative_times
+        || envelope.annotate_action_id
+        || occurrences.is_some()
+        || envelope.field_order.is_some()
+    {
+        return Value::Array(
+            actions.iter().map(|a| annotated(a, now, envelope, occurrences)).collect(),
+        );
+    }
+    json!(actions)
+}
+
+/// The drop-reason histogram of a batch, for `stats_only` requests
+fn stats(report: &FilterReport) -> Value {
+    // ---
+    json!({
+        "input": report.input,
+        "kept": report.kept,
+        "dropped": {
+            "future": report.future,
+            "stale": report.stale,
+            "duplicate": report.duplicate,
+            "validation": report.validation,
+            "denied": report.denied,
+            "low_score": report.low_score,
+            "sampled_out": report.sampled_out,
+            "capped": report.capped,
+        },
+    })
+}
+
+/// Serializes `action` with the computed fields the envelope asks for:
+/// `*_local` timestamp strings in `local_tz`, `days_until_due`, `action_id` and
+/// `occurrence_count`,
+/// timestamps relative to `now` under `relative_times`,
+/// with the keys arranged per `field_order`
+fn annotated(
+    action: &Action,
+    now: DateTime<Utc>,
+    envelope: &Envelope,
+    occurrences: Option<&Occurrences>,
+) -> Value {
+    // ---
+    let mut value = json!(action);
+    if let Some(tz) = envelope.local_tz {
+        value["last_action_time_local"] =
+            json!(action.last_action_time.with_timezone(&tz).to_rfc3339());
+        value["next_action_time_local"] =
+            json!(action.next_action_time.with_timezone(&tz).to_rfc3339());
+    }
+    if envelope.annotate_days_until_due {
+        // Whole days, rounded down, so anything past due is negative
+        let days = (action.next_action_time - now).num_seconds().div_euclid(24 * 60 * 60);
+        value["days_until_due"] = json!(days);
+    }
+    if envelope.annotate_action_id {
+        let mut hasher = StableHasher::new();
+        hasher.write_field(action.entity_id.as_bytes());
+        hasher.write_field(action.next_action_time.to_rfc3339().as_bytes());
+        value["action_id"] = json!(format!("{:016x}", hasher.finish()));
+    }
+    if let Some(occurrences) = occurrences {
+        value["occurrence_count"] = json!(occurrences.of(action));
+    }
+    if envelope.relative_times {
+        let relative = |time: DateTime<Utc>| json!((time - now).num_seconds());
+        value["last_action_time"] = relative(action.last_action_time);
+        value["next_action_time"] = relative(action.next_action_time);
+        if let Some(created_at) = action.created_at {
+            value["created_at"] = relative(created_at);
+        }
+    }
+    match (&envelope.field_order, value) {
+        (Some(order), Value::Object(mut fields)) => {
+            let mut ordered = Map::with_capacity(fields.len());
+            for name in order {
+                if let Some(field) = fields.shift_remove(name) {
+                    ordered.insert(name.clone(), field);
+                }
+            }
+            ordered.extend(fields);
+            Value::Object(ordered)
+        }
+        (_, value) => value,
+    }
+}
+
+/// Number of decoded input records per dedup key, for `occurrence_count`
+struct Occurrences<'a> {
+    config: &'a FilterConfig,
+    counts: HashMap<String, usize>,
+}
+
+impl<'a> Occurrences<'a> {
+    // ---
+    fn count(input: &[Action], config: &'a FilterConfig) -> Self {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for action in input {
+            *counts.entry(dedup_key(action, config)).or_default() += 1;
+        }
+        Occurrences { config, counts }
+    }
+
+    /// Occurrences sharing `action`'s key; at least 1, e.g. for a merged survivor
+    /// whose key fields changed
+    fn of(&self, action: &Action) -> usize {
+        // ---
+        self.counts.get(&dedup_key(action, self.config)).copied().unwrap_or(1)
+    }
+}
+
+/// Effective configuration as JSON, for support diagnostics.
+/// FilterConfig holds no secrets today; strip any sensitive fields here if that changes.
+fn describe_config(config: &FilterConfig) -> Value {
+    // ---
+    json!(config)
+}
+
+/// Deserializes either an array of actions or a map keyed by entity_id.
+/// Offset-less timestamps are interpreted in the configured assumed zone.
+/// Invalid records are reported one at a time or all together per `validation_mode`.
+fn decode_actions(payload: Value, config: &FilterConfig) -> Result<Vec<Action>, FilterError> {
+    // ---
+    let records: Vec<Value> = match payload {
+        Value::Object(map) if map.values().all(Value::is_object) => map
+            .into_iter()
+            .map(|(key, mut value)| {
+                if let Value::Object(fields) = &mut value {
+                    match config.map_key_policy {
+                        MapKeyPolicy::FillMissing => {
+                            fields.entry("entity_id").or_insert(Value::String(key));
+                        }
+                        MapKeyPolicy::Override => {
+                            fields.insert("entity_id".to_string(), Value::String(key));
+                        }
+                    }
+                }
+                value
+            })
+            .collect(),
+        Value::Array(records) => records,
+        other => return serde_json::from_value(other).map_err(FilterError::InvalidPayload),
+    };
+
+    let assumed = config.assumed_offset()?;
+    let date_time = config.date_only_time.unwrap_or(NaiveTime::MIN);
+    let decoded = records.into_iter().enumerate().filter_map(|(index, mut record)| {
+        normalize_timestamps(&mut record, assumed, date_time);
+        apply_priority_aliases(&mut record, &config.priority_aliases);
+        if !apply_missing_last_time(&mut record, index, config.missing_last_time_policy)
+            || !apply_next_time_fallback(&mut record, index, config.next_time_fallback)
+        {
+            return None;
+        }
+        Some((index, serde_json::from_value::<Action>(record)))
+    });
+
+    match config.validation_mode {
+        ValidationMode::FailFast => {
+            decoded.map(|(_, result)| result.map_err(FilterError::InvalidPayload)).collect()
+        }
+        ValidationMode::CollectAll => {
+            let mut actions = Vec::new();
+            let mut errors = Vec::new();
+            for (index, result) in decoded {
+                match result {
+                    Ok(action) => actions.push(action),
+                    Err(err) => errors.push((index, err.to_string())),
+                }
+            }
+            if errors.is_empty() {
+                Ok(actions)
+            } else {
+                Err(FilterError::InvalidRecords(errors))
+            }
+        }
+    }
+}
+
+/// Rewrites a string priority that names one of `aliases` to the aliased `Priority`
+fn apply_priority_aliases(record: &mut Value, aliases: &HashMap<String, Priority>) {
+    // ---
+    let Some(priority) = record.get_mut("priority") else {
+        return;
+    };
+    let Some(name) = priority.as_str().map(str::trim) else {
+        return;
+    };
+    if let Some((_, target)) = aliases.iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)) {
+        *priority = json!(target);
+    }
+}
+
+/// Applies `policy` to a record object lacking a last_action_time (or holding null).
+/// Returns false when the record is dropped.
+fn apply_missing_last_time(
+    record: &mut Value,
+    index: usize,
+    policy: MissingLastTimePolicy,
+) -> bool {
+    // ---
+    let Value::Object(fields) = record else {
+        return true;
+    };
+    if fields.get("last_action_time").is_some_and(|value| !value.is_null()) {
+        return true;
+    }
+
+    match policy {
+        MissingLastTimePolicy::Require => true,
+        MissingLastTimePolicy::NeverActed => {
+            let never = DateTime::<Utc>::UNIX_EPOCH.to_rfc3339();
+            fields.insert("last_action_time".to_string(), json!(never));
+            true
+        }
+        MissingLastTimePolicy::Drop => {
+            tracing::warn!(index, reason = "missing last_action_time", "Dropping record");
+            false
+        }
+    }
+}
+
+/// Applies `fallback` to a (normalized) record whose next_action_time is present but
+/// unparseable while its last_action_time parses. Returns false when the record is dropped.
+fn apply_next_time_fallback(record: &mut Value, index: usize, fallback: NextTimeFallback) -> bool {
+    // ---
+    let parses = |value: &Value| value.as_str().and_then(|t| parse_timestamp(t, utc())).is_some();
+    let (Some(last), Some(next)) = (record.get("last_action_time"), record.get("next_action_time"))
+    else {
+        return true;
+    };
+    if fallback == NextTimeFallback::Fail || parses(next) || !parses(last) {
+        return true;
+    }
+
+    let reason = format!("unparseable next_action_time {next}");
+    match fallback {
+        NextTimeFallback::Drop => {
+            tracing::warn!(index, %reason, "Dropping record");
+            false
+        }
+        NextTimeFallback::Substitute(sentinel) => {
+            tracing::warn!(index, %reason, %sentinel, "Substituting next_action_time");
+            record["next_action_time"] = json!(sentinel.to_rfc3339());
+            true
+        }
+        NextTimeFallback::Fail => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CONFIG_JSON_ENV;
+    use crate::test_support::capture_logs;
+    use anyhow::{ensure, Result};
+
+    /// Fixed evaluation time, so no result depends on the wall clock
+    fn reference_now() -> Result<DateTime<Utc>> {
+        // ---
+        Ok(DateTime::parse_from_rfc3339("2025-06-28T00:00:00Z")?.with_timezone(&Utc))
+    }
+
+    #[test]
+    fn test_invalid_payload_maps_to_problem_json() -> Result<()> {
+        // ---
+        let payload = json!([{
+            "entity_id": "entity_1",
+            "last_action_time": "2025-06-01T00:00:00Z",
+            "next_action_time": "2025-07-01T00:00:00Z",
+            "priority": "unknown"
+        }]);
+
+        let err = match handle_request(payload, reference_now()?, &FilterConfig::default()) {
+            Ok(body) => anyhow::bail!("Expected an error, got {body}"),
+            Err(err) => err,
+        };
+        let problem = err.to_problem_json();
+
+        ensure!(problem["status"] == 400, "Expected status 400, got {}", problem["status"]);
+        ensure!(
+            problem["type"] == "urn:action-filter:invalid_payload",
+            "Unexpected problem type {}",
+            problem["type"]
+        );
+
+        let detail = problem["detail"].as_str().unwrap_or_default();
+        ensure!(
+            detail.contains("unknown variant") && detail.contains("urgent"),
+            "Expected detail to describe the bad priority, got: {detail}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validation_mode_fail_fast_reports_first_error() -> Result<()> {
+        // ---
+        let payload = json!([
+            {
+                "entity_id": "bad_priority",
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": "unknown"
+            },
+            {
+                "entity_id": "bad_time",
+                "last_action_time": "yesterday",
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": "normal"
+            }
+        ]);
+
+        let config =
+            FilterConfig { validation_mode: ValidationMode::FailFast, ..Default::default() };
+        match handle_request(payload.clone(), reference_now()?, &config) {
+            Err(FilterError::InvalidPayload(err)) => {
+                let detail = err.to_string();
+                ensure!(detail.contains("unknown variant"), "Expected the first error: {detail}");
+                ensure!(!detail.contains("yesterday"), "Expected only the first error: {detail}");
+            }
+            other => anyhow::bail!("Expected InvalidPayload, got {other:?}"),
+        }
+
+        let config =
+            FilterConfig { validation_mode: ValidationMode::CollectAll, ..Default::default() };
+        let err = handle_request(payload, reference_now()?, &config).err();
+        let problem = err.map(|e| e.to_problem_json()).unwrap_or_default();
+        ensure!(
+            problem["errors"].as_array().is_some_and(|errors| errors.len() == 2),
+            "Expected both errors to be collected, got {problem}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_next_action_time_is_dropped_with_reason() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let payload = json!([
+            {
+                "entity_id": "corrupt",
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "not-a-date",
+                "priority": "urgent"
+            },
+            {
+                "entity_id": "intact",
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": "normal"
+            }
+        ]);
+
+        let config =
+            FilterConfig { next_time_fallback: NextTimeFallback::Drop, ..Default::default() };
+        let (body, logs) =
+            capture_logs(tracing::Level::WARN, || handle_request(payload.clone(), now, &config));
+        let body = body?;
+        ensure!(body.as_array().map(Vec::len) == Some(1), "Expected one survivor, got {body}");
+        ensure!(body[0]["entity_id"] == "intact", "Unexpected survivor {body}");
+        ensure!(
+            logs.contains("index=0") && logs.contains("unparseable next_action_time"),
+            "Expected the drop reason to be logged: {logs}"
+        );
+
+        let result = handle_request(payload, now, &FilterConfig::default());
+        ensure!(result.is_err(), "Expected the default policy to reject the request");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_action_time_past_chrono_range_is_dropped_as_future() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let payload = json!([
+            {
+                "entity_id": "far_future",
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "300000-01-01T00:00:00Z",
+                "priority": "urgent"
+            },
+            {
+                "entity_id": "intact",
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": "normal"
+            }
+        ]);
+
+        let body = handle_request(payload, now, &FilterConfig::default())?;
+        ensure!(body.as_array().map(Vec::len) == Some(1), "Expected one survivor, got {body}");
+        ensure!(body[0]["entity_id"] == "intact", "Unexpected survivor {body}");
+
+        // Malformed strings are still input errors
+        let payload = json!([{
+            "entity_id": "garbled",
+            "last_action_time": "2025-06-01T00:00:00Z",
+            "next_action_time": "300000-13-45T00:00:00Z",
+            "priority": "normal"
+        }]);
+        let result = handle_request(payload, now, &FilterConfig::default());
+        ensure!(result.is_err(), "Expected a malformed timestamp to be rejected");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_priority_aliases_map_vendor_terms() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let last = (now - chrono::Duration::days(10)).to_rfc3339();
+        let next = (now + chrono::Duration::days(5)).to_rfc3339();
+        let payload = json!([
+            { "entity_id": "e1", "last_action_time": last, "next_action_time": next, "priority": "p1" }
+        ]);
+
+        let config = FilterConfig {
+            priority_aliases: HashMap::from([("P1".to_string(), Priority::Urgent)]),
+            ..Default::default()
+        };
+        let body = handle_request(payload.clone(), now, &config)?;
+        ensure!(body[0]["priority"] == "urgent", "Expected p1 mapped to urgent, got {body}");
+
+        let result = handle_request(payload, now, &FilterConfig::default());
+        ensure!(result.is_err(), "Expected an unmapped alias to be rejected");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_last_action_time_policies() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let next = (now + chrono::Duration::days(5)).to_rfc3339();
+        let payload = json!([
+            { "entity_id": "brand_new", "next_action_time": next, "priority": "urgent" }
+        ]);
+
+        let config = FilterConfig {
+            missing_last_time_policy: MissingLastTimePolicy::NeverActed,
+            ..Default::default()
+        };
+        let body = handle_request(payload.clone(), now, &config)?;
+        ensure!(body.as_array().map(Vec::len) == Some(1), "Expected the record kept, got {body}");
+        ensure!(body[0]["entity_id"] == "brand_new", "Unexpected survivor {body}");
+        ensure!(
+            body[0]["last_action_time"] == "1970-01-01T00:00:00Z",
+            "Expected the epoch as last_action_time, got {body}"
+        );
+
+        let config = FilterConfig {
+            missing_last_time_policy: MissingLastTimePolicy::Drop,
+            ..Default::default()
+        };
+        let body = handle_request(payload.clone(), now, &config)?;
+        ensure!(body == json!([]), "Expected the record dropped, got {body}");
+
+        let result = handle_request(payload, now, &FilterConfig::default());
+        ensure!(result.is_err(), "Expected the default policy to reject the request");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_shaped_payload_uses_key_as_entity_id() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let last = (now - chrono::Duration::days(10)).to_rfc3339();
+        let next = (now + chrono::Duration::days(10)).to_rfc3339();
+        let payload = json!({
+            "entity_1": { "last_action_time": last, "next_action_time": next, "priority": "urgent" },
+            "entity_2": {
+                "entity_id": "explicit_id",
+                "last_action_time": last,
+                "next_action_time": next,
+                "priority": "normal"
+            }
+        });
+
+        let body = handle_request(payload.clone(), now, &FilterConfig::default())?;
+        let actions: Vec<Action> = serde_json::from_value(body)?;
+        let ids: Vec<&str> = actions.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["entity_1", "explicit_id"], "Unexpected entity_ids {ids:?}");
+
+        let config = FilterConfig { map_key_policy: MapKeyPolicy::Override, ..Default::default() };
+        let body = handle_request(payload, now, &config)?;
+        let actions: Vec<Action> = serde_json::from_value(body)?;
+        let ids: Vec<&str> = actions.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["entity_1", "entity_2"], "Expected keys to override, got {ids:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_with_map_shaped_actions_only() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let last = (now - chrono::Duration::days(10)).to_rfc3339();
+        let next = (now + chrono::Duration::days(10)).to_rfc3339();
+        let payload = json!({
+            "actions": {
+                "e1": { "last_action_time": last, "next_action_time": next, "priority": "urgent" }
+            }
+        });
+
+        let body = handle_request(payload, now, &FilterConfig::default())?;
+        let actions: Vec<Action> = serde_json::from_value(body.clone())?;
+        let ids: Vec<&str> = actions.iter().map(|a| a.entity_id.as_str()).collect();
+        ensure!(ids == ["e1"], "Expected the map's entity, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_config_reflects_env_overrides() -> Result<()> {
+        // ---
+        let config = FilterConfig::from_env_with(|name| {
+            (name == CONFIG_JSON_ENV).then(|| r#"{"max_future_skew_minutes": 5}"#.to_string())
+        })?;
+
+        let body = handle_request(json!({"action": "describe_config"}), reference_now()?, &config)?;
+
+        ensure!(body["max_future_skew_minutes"] == 5, "Expected env override, got {body}");
+        ensure!(body["dedup_strategy"] == "last_seen", "Expected default strategy, got {body}");
+
+        let err =
+            handle_request(json!({"action": "reboot"}), reference_now()?, &FilterConfig::default())
+                .err();
+        ensure!(
+            err.is_some_and(|e| e.status() == 400),
+            "Expected unknown actions to be rejected with status 400"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ids_only_returns_entity_ids() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let body = handle_request(
+            json!({"actions": actions, "ids_only": true}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        ensure!(body == json!(["entity_1", "entity_3"]), "Unexpected ids_only body {body}");
+
+        let body = handle_request(json!({"actions": actions}), now, &FilterConfig::default())?;
+        ensure!(body[0]["entity_id"] == "entity_1", "Expected full actions by default, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_previous_ids_returns_delta() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let body = handle_request(
+            json!({"actions": actions, "previous_ids": ["entity_1", "entity_2"], "ids_only": true}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        ensure!(
+            body == json!({"added": ["entity_3"], "removed": ["entity_2"], "still_present": ["entity_1"]}),
+            "Unexpected delta body {body}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_urgent_overdue_only_envelope() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next: &str, priority: &str| {
+            json!({
+                "entity_id": id,
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": next,
+                "priority": priority
+            })
+        };
+        let actions = json!([
+            action("late_urgent", "2025-06-25T00:00:00Z", "urgent"),
+            action("late_normal", "2025-06-25T00:00:00Z", "normal"),
+            action("soon_urgent", "2025-07-01T00:00:00Z", "urgent"),
+        ]);
+
+        let body = handle_request(
+            json!({"actions": actions, "urgent_overdue_only": true, "ids_only": true}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        ensure!(body == json!(["late_urgent"]), "Unexpected body {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_tz_adds_local_strings() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let body = handle_request(
+            json!({"actions": actions, "local_tz": "America/New_York"}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        let first = &body[0];
+        ensure!(first["next_action_time"] == "2025-07-01T00:00:00Z", "Expected UTC kept: {first}");
+        ensure!(
+            first["next_action_time_local"] == "2025-06-30T20:00:00-04:00",
+            "Expected an EDT local string: {first}"
+        );
+        ensure!(
+            first["last_action_time_local"] == "2025-05-31T20:00:00-04:00",
+            "Expected an EDT local string: {first}"
+        );
+
+        let err = handle_request(
+            json!({"actions": [], "local_tz": "Mars/Olympus_Mons"}),
+            now,
+            &FilterConfig::default(),
+        )
+        .err();
+        ensure!(err.is_some_and(|e| e.status() == 400), "Expected unknown zones to be rejected");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_days_until_due_annotation() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next: &str| {
+            json!({
+                "entity_id": id,
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": next,
+                "priority": "normal"
+            })
+        };
+        let actions = json!([
+            action("ahead", "2025-07-08T00:00:00Z"),
+            action("overdue", "2025-06-27T18:00:00Z"),
+        ]);
+
+        let body = handle_request(
+            json!({ "actions": actions, "annotate_days_until_due": true }),
+            now,
+            &FilterConfig::default(),
+        )?;
+        ensure!(body[0]["days_until_due"] == 10, "Expected 10 days, got {}", body[0]);
+        ensure!(body[1]["days_until_due"] == -1, "Expected -1 once overdue, got {}", body[1]);
+
+        let plain = handle_request(json!({ "actions": actions }), now, &FilterConfig::default())?;
+        ensure!(plain[0].get("days_until_due").is_none(), "Expected no annotation by default");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_entity_names_drop_reasons() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last: &str| {
+            json!({
+                "entity_id": id,
+                "last_action_time": last,
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": "normal"
+            })
+        };
+        let actions = json!([
+            action("entity_3", "2025-06-01T00:00:00Z"),
+            action("entity_1", "2025-06-01T00:00:00Z"),
+            action("entity_3", "2025-06-27T00:00:00Z"),
+            action("entity_3", "2025-06-02T00:00:00Z"),
+        ]);
+
+        let body = handle_request(
+            json!({ "actions": actions, "explain_entity": "entity_3" }),
+            now,
+            &FilterConfig::default(),
+        )?;
+        let expected = json!({
+            "entity_id": "entity_3",
+            "occurrences": [
+                {"index": 0, "outcome": "duplicate", "kept_index": 3},
+                {"index": 2, "outcome": "stale"},
+                {"index": 3, "outcome": "kept"},
+            ]
+        });
+        ensure!(body == expected, "Unexpected explanation {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_dedup_strategy_overrides_config() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |priority: &str| {
+            json!({
+                "entity_id": "duplicate",
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": priority
+            })
+        };
+        let actions = json!([action("urgent"), action("normal")]);
+        let config = FilterConfig::default();
+
+        let body = handle_request(json!({ "actions": actions }), now, &config)?;
+        ensure!(body[0]["priority"] == "normal", "Expected the last occurrence, got {body}");
+
+        let payload = json!({ "actions": actions, "dedup_strategy": "highest_priority" });
+        let body = handle_request(payload, now, &config)?;
+        ensure!(body[0]["priority"] == "urgent", "Expected the urgent occurrence, got {body}");
+
+        let payload = json!({ "actions": actions, "dedup_strategy": "loudest" });
+        let err = match handle_request(payload, now, &config) {
+            Ok(body) => anyhow::bail!("Expected an error, got {body}"),
+            Err(err) => err,
+        };
+        let detail = err.to_string();
+        ensure!(err.status() == 400, "Expected status 400, got {}", err.status());
+        ensure!(detail.contains("highest_priority"), "Expected the valid set, got: {detail}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative_times_are_seconds_from_now() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let actions = json!([{
+            "entity_id": "ahead",
+            "last_action_time": "2025-06-18T00:00:00Z",
+            "next_action_time": "2025-07-08T00:00:00Z",
+            "priority": "normal"
+        }]);
+
+        let body = handle_request(
+            json!({ "actions": actions, "relative_times": true }),
+            now,
+            &FilterConfig::default(),
+        )?;
+        let day = 24 * 60 * 60;
+        ensure!(body[0]["next_action_time"] == 10 * day, "Expected +10 days, got {}", body[0]);
+        ensure!(body[0]["last_action_time"] == -10 * day, "Expected -10 days, got {}", body[0]);
+
+        let plain = handle_request(json!({ "actions": actions }), now, &FilterConfig::default())?;
+        ensure!(plain[0]["next_action_time"].is_string(), "Expected RFC3339 by default");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunk_size_splits_in_order() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let actions: Vec<Value> = (1..=5)
+            .map(|day| {
+                json!({
+                    "entity_id": format!("entity_{day}"),
+                    "last_action_time": "2025-06-01T00:00:00Z",
+                    "next_action_time": format!("2025-07-0{day}T00:00:00Z"),
+                })
+            })
+            .collect();
+        let payload = json!({ "actions": actions, "chunk_size": 2, "ids_only": true });
+
+        let body = handle_request(payload, now, &FilterConfig::default())?;
+        let expected = json!({
+            "chunks": [["entity_1", "entity_2"], ["entity_3", "entity_4"], ["entity_5"]]
+        });
+        ensure!(body == expected, "Unexpected chunks {body}");
+
+        let zero = handle_request(
+            json!({ "actions": [], "chunk_size": 0 }),
+            now,
+            &FilterConfig::default(),
+        );
+        ensure!(
+            zero.is_err_and(|e| e.status() == 400),
+            "Expected a zero chunk_size to be rejected"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_payload_bytes() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let payload: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let size = serde_json::to_string(&payload)?.len();
+
+        let tight = FilterConfig { max_payload_bytes: Some(size - 1), ..Default::default() };
+        let problem = match handle_request(payload.clone(), now, &tight) {
+            Ok(body) => anyhow::bail!("Expected an oversized payload to fail, got {body}"),
+            Err(err) => err.to_problem_json(),
+        };
+        ensure!(problem["type"] == "urn:action-filter:payload_too_large", "Got {problem}");
+        ensure!(problem["status"] == 413 && problem["size"] == size, "Got {problem}");
+
+        let roomy = FilterConfig { max_payload_bytes: Some(size), ..Default::default() };
+        let body = handle_request(payload, now, &roomy)?;
+        ensure!(body.as_array().is_some_and(|a| !a.is_empty()), "Expected actions, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batches_keep_order_at_any_concurrency() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let batch = |id: &str| {
+            json!([{
+                "entity_id": id,
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": "2025-07-01T00:00:00Z",
+                "priority": "normal"
+            }])
+        };
+        let mut batches: Vec<Value> = (0..6).map(|i| batch(&format!("batch_{i}"))).collect();
+        batches[3] = json!([{ "entity_id": "broken", "priority": "unknown" }]);
+        let payload = json!({ "batches": batches, "ids_only": true });
+
+        let sequential = handle_request(payload.clone(), now, &FilterConfig::default())?;
+        let config = FilterConfig { batch_concurrency: Some(4), ..Default::default() };
+        let concurrent = handle_request(payload, now, &config)?;
+
+        ensure!(sequential == concurrent, "Expected identical results: {concurrent}");
+        let results = concurrent["batches"].as_array().cloned().unwrap_or_default();
+        ensure!(results.len() == 6, "Expected one result per batch, got {concurrent}");
+        ensure!(results[0] == json!(["batch_0"]), "Unexpected first batch {}", results[0]);
+        ensure!(results[5] == json!(["batch_5"]), "Unexpected last batch {}", results[5]);
+        ensure!(results[3]["status"] == 400, "Expected the bad batch's problem: {}", results[3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_error_policies() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let good = json!([{
+            "entity_id": "good",
+            "last_action_time": "2025-06-01T00:00:00Z",
+            "next_action_time": "2025-07-01T00:00:00Z"
+        }]);
+        let bad = json!([{ "entity_id": "broken", "priority": "unknown" }]);
+        let payload = json!({ "batches": [good, bad], "ids_only": true });
+        let run = |batch_error_policy: BatchErrorPolicy| {
+            let config = FilterConfig { batch_error_policy, ..Default::default() };
+            handle_request(payload.clone(), now, &config)
+        };
+
+        let err = run(BatchErrorPolicy::FailAll).err();
+        ensure!(
+            err.as_ref().is_some_and(|e| e.code() == "invalid_payload"),
+            "Expected the bad batch to fail the request, got {err:?}"
+        );
+
+        let body = run(BatchErrorPolicy::SkipBatch)?;
+        ensure!(body == json!({ "batches": [["good"]] }), "Unexpected skip body {body}");
+
+        let body = run(BatchErrorPolicy::ReportPerBatch)?;
+        ensure!(body["results"] == json!([["good"], null]), "Unexpected results {body}");
+        ensure!(
+            body["errors"][0].is_null() && body["errors"][1]["status"] == 400,
+            "Unexpected errors {body}"
+        );
+
+        let body = run(BatchErrorPolicy::Inline)?;
+        ensure!(body["batches"][1]["status"] == 400, "Expected an inline problem, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_order_arranges_action_keys() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let body = handle_request(
+            json!({"actions": actions, "field_order": ["priority", "next_action_time", "bogus"]}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        let keys: Vec<&str> =
+            body[0].as_object().into_iter().flatten().map(|(k, _)| k.as_str()).collect();
+        ensure!(
+            keys == ["priority", "next_action_time", "entity_id", "last_action_time"],
+            "Unexpected key order {keys:?}"
+        );
+        let text = body.to_string();
+        ensure!(text.starts_with(r#"[{"priority":"#), "Expected priority first in {text}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_response_round_trips_through_gzip() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let actions: Vec<Value> = (0..200)
+            .map(|i| {
+                json!({
+                    "entity_id": format!("entity_{i}"),
+                    "last_action_time": (now - chrono::Duration::days(10)).to_rfc3339(),
+                    "next_action_time": (now + chrono::Duration::days(5)).to_rfc3339(),
+                })
+            })
+            .collect();
+        let config = FilterConfig::default();
+        let plain = handle_request_text(json!({ "actions": actions }), now, &config)?;
+
+        let text = handle_request_text(
+            json!({ "actions": actions, "accept_encoding": "br, GZIP;q=0.8" }),
+            now,
+            &config,
+        )?;
+        let response: Value = serde_json::from_str(&text)?;
+        ensure!(response["headers"]["Content-Encoding"] == "gzip", "Unexpected {response}");
+        ensure!(response["isBase64Encoded"] == true, "Expected a base64 body");
+        let compressed = BASE64_STANDARD.decode(response["body"].as_str().unwrap_or_default())?;
+        ensure!(compressed.len() < plain.len(), "Expected compression to shrink the body");
+        let mut decompressed = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(compressed.as_slice()),
+            &mut decompressed,
+        )?;
+        ensure!(decompressed == plain, "Expected the gzipped body to round-trip");
+
+        let small = json!({ "actions": &actions[..1], "accept_encoding": "gzip" });
+        let text = handle_request_text(small, now, &config)?;
+        ensure!(text.starts_with('['), "Expected a small response to stay plain, got {text}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_action_id_is_stable_across_runs() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+        let payload = json!({"actions": actions, "annotate_action_id": true});
+
+        let first = handle_request(payload.clone(), now, &FilterConfig::default())?;
+        let second = handle_request(payload, now, &FilterConfig::default())?;
+        let ids = |body: &Value| -> Vec<String> {
+            body.as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|a| a["action_id"].as_str().map(str::to_string))
+                .collect()
+        };
+        ensure!(ids(&first).len() == 2, "Expected an action_id per action, got {first}");
+        ensure!(ids(&first) == ids(&second), "Expected identical ids across runs");
+        ensure!(ids(&first)[0] != ids(&first)[1], "Expected distinct actions to differ");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_due_returns_buckets() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let body = handle_request(
+            json!({"actions": actions, "group_by_due": true, "ids_only": true}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        ensure!(
+            body == json!({"this_week": ["entity_1"], "this_month": ["entity_3"]}),
+            "Unexpected grouped body {body}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_due_histogram_envelope() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let body = handle_request(
+            json!({"actions": actions, "due_histogram_days": 30}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        let total: u64 = body["histogram"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|b| b["count"].as_u64())
+            .sum();
+        ensure!(total == 2, "Expected both survivors counted, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_after_entity_id_pages_through_survivors() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let last = (now - chrono::Duration::days(10)).to_rfc3339();
+        let next = (now + chrono::Duration::days(5)).to_rfc3339();
+        let actions: Vec<Value> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|id| json!({"entity_id": id, "last_action_time": last, "next_action_time": next}))
+            .collect();
+
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let mut request = json!({"actions": actions, "ids_only": true, "page_size": 3});
+            if let Some(after) = &cursor {
+                request["after_entity_id"] = json!(after);
+            }
+            let page = handle_request(request, now, &FilterConfig::default())?;
+            let Some(last_id) = page.as_array().and_then(|ids| ids.last()).cloned() else {
+                break;
+            };
+            cursor = last_id.as_str().map(str::to_string);
+            pages.push(page);
+        }
+        ensure!(pages == [json!(["a", "b", "c"]), json!(["d"])], "Unexpected pages {pages:?}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_occurrence_count_annotates_survivors() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last_days: i64| {
+            json!({
+                "entity_id": id,
+                "last_action_time": (now - chrono::Duration::days(last_days)).to_rfc3339(),
+                "next_action_time": (now + chrono::Duration::days(5)).to_rfc3339(),
+            })
+        };
+        let actions =
+            json!([action("dup", 10), action("dup", 11), action("single", 10), action("dup", 12)]);
+
+        let body = handle_request(
+            json!({"actions": actions, "occurrence_count": true}),
+            now,
+            &FilterConfig::default(),
+        )?;
+        let counts: BTreeMap<&str, &Value> = body
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|a| Some((a["entity_id"].as_str()?, &a["occurrence_count"])))
+            .collect();
+        ensure!(counts.get("dup") == Some(&&json!(3)), "Expected 3 for dup, got {body}");
+        ensure!(counts.get("single") == Some(&&json!(1)), "Expected 1 for single, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_version_wraps_actions() -> Result<()> {
+        // ---
+        let actions: Value =
+            serde_json::from_str(include_str!("../testdata/01_sample-input.json"))?;
+        let now = reference_now()?;
+
+        let config = FilterConfig { response_version: true, ..Default::default() };
+        let body = handle_request(actions.clone(), now, &config)?;
+        ensure!(body["schema_version"] == 1, "Expected schema_version 1, got {body}");
+        ensure!(body["actions"][0]["entity_id"] == "entity_1", "Expected wrapped actions: {body}");
+
+        let body = handle_request(actions, now, &FilterConfig::default())?;
+        ensure!(body.is_array(), "Expected a bare array by default, got {body}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pretty_flag_indents_output() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = json!({
+            "entity_id": "entity_1",
+            "last_action_time": (now - chrono::Duration::days(10)).to_rfc3339(),
+            "next_action_time": (now + chrono::Duration::days(1)).to_rfc3339(),
+            "priority": "urgent"
+        });
+        let config = FilterConfig::default();
+
+        let compact = handle_request_text(json!({ "actions": [action] }), now, &config)?;
+        ensure!(!compact.contains('\n'), "Expected compact output, got {compact}");
+
+        let pretty =
+            handle_request_text(json!({ "actions": [action], "pretty": true }), now, &config)?;
+        ensure!(pretty.contains("\n  {\n    \""), "Expected indented output, got {pretty}");
+        let reparsed: Value = serde_json::from_str(&pretty)?;
+        ensure!(reparsed == serde_json::from_str::<Value>(&compact)?, "Expected the same body");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_override_audit_envelope() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let payload = json!({
+            "actions": [{
+                "entity_id": "entity_5",
+                "last_action_time": (now - chrono::Duration::days(10)).to_rfc3339(),
+                "next_action_time": (now + chrono::Duration::days(5)).to_rfc3339(),
+                "priority": "normal"
+            }],
+            "include_override_audit": true
+        });
+        let config = FilterConfig::from_json(r#"{"priority_overrides": {"entity_5": "urgent"}}"#)?;
+
+        let body = handle_request(payload, now, &config)?;
+        ensure!(
+            body["override_audit"]
+                == json!([{"entity_id": "entity_5", "from": "normal", "to": "urgent"}]),
+            "Unexpected audit {body}"
+        );
+        ensure!(
+            body["actions"][0]["priority"] == "urgent",
+            "Expected the override applied: {body}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_audit_names_kept_and_dropped() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, next: &str| {
+            json!({
+                "entity_id": id,
+                "last_action_time": "2025-06-01T00:00:00Z",
+                "next_action_time": next,
+                "priority": "normal"
+            })
+        };
+        let actions = json!([
+            action("dup", "2025-07-01T00:00:00Z"),
+            action("single", "2025-07-02T00:00:00Z"),
+            action("dup", "2025-07-03T00:00:00Z"),
+        ]);
+        let config = FilterConfig::default();
+
+        let body =
+            handle_request(json!({ "actions": actions, "dedup_audit": true }), now, &config)?;
+        ensure!(body["actions"].as_array().map(Vec::len) == Some(2), "Unexpected body {body}");
+        ensure!(
+            body["dedup_audit"]
+                == json!([{ "entity_id": "dup", "kept_index": 2, "dropped_indices": [0] }]),
+            "Unexpected audit {}",
+            body["dedup_audit"]
+        );
+
+        let plain = handle_request(json!({ "actions": actions }), now, &config)?;
+        ensure!(plain.is_array(), "Expected no wrapper without the flag, got {plain}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_only_returns_histogram() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let action = |id: &str, last: &str, next: &str| json!({ "entity_id": id, "last_action_time": last, "next_action_time": next });
+        let payload = json!({
+            "stats_only": true,
+            "actions": [
+                action("kept", "2025-06-01T00:00:00Z", "2025-07-01T00:00:00Z"),
+                action("dup", "2025-06-01T00:00:00Z", "2025-07-01T00:00:00Z"),
+                action("dup", "2025-06-02T00:00:00Z", "2025-07-02T00:00:00Z"),
+                action("future", "2025-06-01T00:00:00Z", "2025-12-01T00:00:00Z"),
+                action("stale", "2025-06-27T00:00:00Z", "2025-07-01T00:00:00Z"),
+                action("", "2025-06-01T00:00:00Z", "2025-07-01T00:00:00Z"),
+            ]
+        });
+
+        let body = handle_request(payload, now, &FilterConfig::default())?;
+        let expected = json!({
+            "input": 6,
+            "kept": 2,
+            "dropped": {
+                "future": 1,
+                "stale": 1,
+                "duplicate": 1,
+                "validation": 1,
+                "denied": 0,
+                "low_score": 0,
+                "sampled_out": 0,
+                "capped": 0,
+            },
+        });
+        ensure!(body == expected, "Unexpected histogram {body}");
+
+        // A histogram cut short by the deadline says so
+        let actions: Vec<Value> = (0..2 * 1024)
+            .map(|i| action(&format!("entity_{i}"), "2025-06-01T00:00:00Z", "2025-07-01T00:00:00Z"))
+            .collect();
+        let payload = json!({ "stats_only": true, "actions": actions });
+        let config = FilterConfig { deadline_ms: Some(0), ..Default::default() };
+        let body = handle_request(payload, now, &config)?;
+        ensure!(body["truncated"] == true, "Expected a truncated flag, got {body}");
+        ensure!(
+            body["input"] == 2048 && body["dropped"].is_object(),
+            "Unexpected histogram {body}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata_passes_through_untouched() -> Result<()> {
+        // ---
+        let now = reference_now()?;
+        let metadata = json!({"source": "crm", "score": 0.75, "refs": [1, null, {"deep": true}]});
+        let payload = json!([{
+            "entity_id": "entity_1",
+            "last_action_time": "2025-06-01T00:00:00Z",
+            "next_action_time": "2025-07-01T00:00:00Z",
+            "priority": "urgent",
+            "metadata": metadata
+        }]);
+
+        let body = handle_request(payload, now, &FilterConfig::default())?;
+
+        ensure!(body[0]["metadata"] == metadata, "Expected metadata unchanged, got {body}");
+        ensure!(
+            handle_request(json!([]), now, &FilterConfig::default())? == json!([]),
+            "Expected an empty batch to stay empty"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+// Stable hashing helpers.
+//
+// `std::collections::hash_map::DefaultHasher` is not guaranteed to produce the same
+// output across Rust releases, so anything we hash for identity purposes goes through
+// this FNV-1a implementation instead.
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Incremental FNV-1a (64-bit) hasher with output that is stable across builds
+#[derive(Clone, Debug)]
+pub(crate) struct StableHasher(u64);
+
+impl StableHasher {
+    // ---
+    pub(crate) fn new() -> Self {
+        StableHasher(FNV_OFFSET_BASIS)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        // ---
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Writes a length-prefixed field so adjacent fields cannot run together
+    pub(crate) fn write_field(&mut self, bytes: &[u8]) {
+        // ---
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -1,4 +1,38 @@
 // EMBP Gateway - re-export domain entities
+mod business_days;
+mod clock;
+mod config;
+mod diff;
 mod domain;
+mod error;
+mod filter;
+mod handler;
+mod hashing;
+mod metrics;
+mod report;
+mod timestamp;
 
-pub use domain::{Action, Priority};
+#[cfg(test)]
+mod test_support;
+
+pub use clock::{Clock, FixedClock, SystemClock};
+pub use config::{
+    cached_config, reset_config_cache, AdaptivePriority, AtNowPolicy, BatchErrorPolicy,
+    ConfigCache, DedupKey, DedupStrategy, EmptyIdPolicy, EmptyInputPolicy, FilterConfig,
+    Granularity, LengthPolicy, MalformedIdPolicy, MapKeyPolicy, MissingLastTimePolicy,
+    MissingScorePolicy, NextTimeFallback, PriorityTieBreak, SampleConfig, SkewPolicy, SortKey,
+    TieBreak, ValidationAction, ValidationMode, ZeroDurationPolicy, CONFIG_FILE_ENV,
+    CONFIG_JSON_ENV, DEFAULT_FUTURE_WINDOW_DAYS, DEFAULT_GZIP_MIN_BYTES, DEFAULT_PAST_WINDOW_DAYS,
+};
+pub use diff::{diff_results, ResultDiff};
+pub use domain::{estimate_batch_bytes, Action, DueBucket, Priority};
+pub use error::FilterError;
+pub use filter::{
+    actions_after, assert_output_subset, due_histogram, explain_entity, filter_actions_iter,
+    filter_only_iter, group_by_due_bucket, merge_results, next_wakeup, partition_due,
+    passes_time_filters, process_actions, process_actions_then, process_actions_with_report,
+    remap_entity_ids, sort_actions, try_process_actions, urgent_overdue,
+};
+pub use handler::{handle_request, handle_request_text, RESPONSE_SCHEMA_VERSION};
+pub use metrics::metrics_text;
+pub use report::{DedupAuditEntry, FilterReport, OccurrenceDecision, PriorityOverride};
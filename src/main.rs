@@ -1,21 +1,39 @@
-mod domain;
-
-use chrono::{Duration, Utc};
-use lambda_runtime::{service_fn, Error, LambdaEvent};
-use serde_json::{json, Value};
-use std::collections::HashMap;
-
-// Import domain entities from local domain module
-use domain::Action; // Priority
+#[cfg(feature = "metrics")]
+use aws_lambda_action_filter::metrics_text;
+use aws_lambda_action_filter::{cached_config, handle_request_text, Clock, SystemClock};
+use lambda_runtime::{service_fn, Context, Error, LambdaEvent};
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::time::SystemTime;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Collector endpoint read by the `otel` feature; spans are only exported when it is set
+#[cfg(feature = "otel")]
+const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Provider behind the `otel` layer, kept so each invocation can flush its spans
+#[cfg(feature = "otel")]
+static OTEL_PROVIDER: std::sync::OnceLock<opentelemetry_sdk::trace::TracerProvider> =
+    std::sync::OnceLock::new();
+
+/// Listen address (e.g. "127.0.0.1:9090") read by the `metrics` feature; `/metrics`
+/// is only served when it is set
+#[cfg(feature = "metrics")]
+const METRICS_ADDR_ENV: &str = "ACTION_FILTER_METRICS_ADDR";
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     // ---
-    tracing_subscriber::fmt()
-        .with_env_filter("info") // or customize with RUST_LOG
-        .with_target(false)
-        .without_time()
-        .init();
+    let registry = tracing_subscriber::registry()
+        .with(EnvFilter::new("info")) // or customize with RUST_LOG
+        .with(tracing_subscriber::fmt::layer().with_target(false).without_time());
+    #[cfg(feature = "otel")]
+    let registry = registry.with(otel_layer(std::env::var(OTLP_ENDPOINT_ENV).ok())?);
+    registry.init();
+    #[cfg(feature = "metrics")]
+    serve_metrics()?;
 
     tracing::info!("Lambda starting...");
 
@@ -24,197 +42,191 @@ async fn main() -> Result<(), Error> {
     Ok(())
 }
 
-/// Lambda handler that processes action filtering requests
-async fn filter_actions(event: LambdaEvent<Value>) -> Result<Value, Error> {
+/// Lambda handler that processes action filtering requests.
+/// Recoverable failures are returned as RFC 7807 problem+json bodies.
+/// The body is passed through as raw JSON so a `"pretty"` request keeps its formatting.
+async fn filter_actions(event: LambdaEvent<Value>) -> Result<Box<RawValue>, Error> {
     // ---
+    let (value, context) = event.into_parts();
     tracing::info!(
-        "Processing event with {} actions",
-        event.payload.as_array().map(|v| v.len()).unwrap_or(0),
+        request_id = request_id(&context),
+        remaining_ms = ?remaining_ms(&context),
+        "Handling request"
     );
 
-    let (value, _context) = event.into_parts();
-    let input: Vec<Action> = serde_json::from_value(value)?;
+    let now = SystemClock.now();
+    // Filtering is CPU-bound (and may fan out over threads), so keep it off the executor
+    let result = tokio::task::spawn_blocking(move || {
+        cached_config().and_then(|config| handle_request_text(value, now, &config))
+    })
+    .await?;
+    let text = match result {
+        Ok(text) => text,
+        Err(err) => {
+            tracing::warn!("Rejecting request: {err}");
+            err.to_problem_json().to_string()
+        }
+    };
+    #[cfg(feature = "otel")]
+    flush_spans().await;
+    Ok(RawValue::from_string(text)?)
+}
 
-    let actions = process_actions(input);
+/// OpenTelemetry layer exporting spans over OTLP/gRPC to `endpoint` (the value of
+/// `OTEL_EXPORTER_OTLP_ENDPOINT`), or None without one. Must be called inside the Tokio runtime.
+#[cfg(feature = "otel")]
+fn otel_layer<S>(
+    endpoint: Option<String>,
+) -> Result<Option<impl tracing_subscriber::Layer<S>>, Error>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    // ---
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let Some(endpoint) = endpoint else {
+        return Ok(None);
+    };
+    let exporter =
+        opentelemetry_otlp::SpanExporter::builder().with_tonic().with_endpoint(endpoint).build()?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .build();
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    let _ = OTEL_PROVIDER.set(provider.clone());
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
 
-    tracing::info!("Returning {} filtered actions", actions.len());
+/// Exports the spans batched so far. Lambda freezes the process between invocations,
+/// which would otherwise strand them in the batch queue.
+#[cfg(feature = "otel")]
+async fn flush_spans() {
+    // ---
+    let Some(provider) = OTEL_PROVIDER.get() else {
+        return;
+    };
+    // force_flush blocks until the batch processor has exported, so wait off the executor
+    match tokio::task::spawn_blocking(|| provider.force_flush()).await {
+        Ok(results) => {
+            for err in results.into_iter().filter_map(Result::err) {
+                tracing::warn!("Span export failed: {err}");
+            }
+        }
+        Err(err) => tracing::warn!("Span flush failed: {err}"),
+    }
+}
+
+/// Serves the filter counters in Prometheus text format at `GET /metrics` on
+/// `ACTION_FILTER_METRICS_ADDR`, from a background thread, for long-lived local runs
+/// (e.g. `cargo lambda watch`). Does nothing when the variable is unset.
+#[cfg(feature = "metrics")]
+fn serve_metrics() -> Result<(), Error> {
+    // ---
+    use std::io::{BufRead, BufReader, Write};
+
+    let Ok(addr) = std::env::var(METRICS_ADDR_ENV) else {
+        return Ok(());
+    };
+    let listener = std::net::TcpListener::bind(&addr)?;
+    tracing::info!(%addr, "Serving /metrics");
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            let served = BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .and_then(|_| (&stream).write_all(metrics_response(&request_line).as_bytes()));
+            if let Err(err) = served {
+                tracing::debug!("Metrics request failed: {err}");
+            }
+        }
+    });
+    Ok(())
+}
 
-    Ok(json!(actions))
+/// HTTP response to one metrics request, given its request line
+#[cfg(feature = "metrics")]
+fn metrics_response(request_line: &str) -> String {
+    // ---
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", metrics_text()),
+        _ => ("404 Not Found", String::new()),
+    };
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
 }
 
-/// Filters and sorts actions according to business rules:
-/// - Filters out actions with next_action_time > 90 days from now
-/// - Filters out actions with last_action_time < 7 days ago  
-/// - Deduplicates by entity_id (keeping the last occurrence)
-/// - Sorts by priority (Urgent first, then Normal)
-fn process_actions(input: Vec<Action>) -> Vec<Action> {
+/// Request id for log correlation; "local" when invoked without an AWS-provided context
+fn request_id(context: &Context) -> &str {
     // ---
-    let today = Utc::now();
-    let threshold_90 = (today + Duration::days(90)).date_naive(); // For next_action_time
-    let threshold_7 = (today - Duration::days(7)).date_naive(); // For last_action_time
-
-    let filtered: Vec<Action> = input
-        .into_iter()
-        .filter(|a| a.next_action_time.date_naive() <= threshold_90)
-        .filter(|a| a.last_action_time.date_naive() < threshold_7)
-        .collect();
-
-    let mut map: HashMap<String, &Action> = HashMap::new();
-    for action in &filtered {
-        map.insert(action.entity_id.clone(), action); // Last occurrence wins
+    match context.request_id.as_str() {
+        "" => "local",
+        id => id,
     }
+}
 
-    let mut deduped: Vec<Action> = map.into_values().cloned().collect();
-    deduped.sort_by(|a, b| a.priority.cmp(&b.priority));
-    deduped
+/// Milliseconds left before the invocation deadline.
+/// None when the context carries no deadline or it has already passed.
+fn remaining_ms(context: &Context) -> Option<u128> {
+    // ---
+    if context.deadline == 0 {
+        return None;
+    }
+    context.deadline().duration_since(SystemTime::now()).ok().map(|left| left.as_millis())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::{ensure, Result};
-    use chrono::DateTime;
-    use domain::Priority;
+    use serde_json::json;
 
-    /// Helper function to parse RFC3339 date strings for tests
-    fn parse_date(s: &str) -> Result<DateTime<Utc>> {
+    #[tokio::test]
+    async fn test_handler_runs_without_aws_context() -> Result<()> {
         // ---
-        let temp = DateTime::parse_from_rfc3339(s)?;
-        Ok(temp.with_timezone(&Utc))
-    }
+        let context = Context::default();
+        ensure!(request_id(&context) == "local", "Expected a placeholder request id");
+        ensure!(remaining_ms(&context).is_none(), "Expected no deadline");
 
-    #[test]
-    fn test_filter_and_sort_actions() -> Result<()> {
-        // ---
-        let input = vec![
-            Action {
-                entity_id: "entity_1".to_string(),
-                last_action_time: parse_date("2025-06-20T00:00:00Z")?,
-                next_action_time: parse_date("2025-07-10T00:00:00Z")?,
-                priority: Priority::Urgent,
-            },
-            Action {
-                entity_id: "entity_2".to_string(),
-                last_action_time: parse_date("2025-06-01T00:00:00Z")?,
-                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
-                priority: Priority::Normal,
-            },
-            Action {
-                entity_id: "entity_3".to_string(),
-                last_action_time: parse_date("2025-03-01T00:00:00Z")?,
-                next_action_time: parse_date("2026-01-01T00:00:00Z")?,
-                priority: Priority::Urgent, // should be excluded (next_action too far)
-            },
-            Action {
-                entity_id: "entity_4".to_string(),
-                last_action_time: parse_date("2025-06-25T00:00:00Z")?,
-                next_action_time: parse_date("2025-07-10T00:00:00Z")?,
-                priority: Priority::Urgent, // should be excluded (last_action < 7 days ago)
-            },
-        ];
-
-        let output = process_actions(input);
-
-        // Verify we have exactly 2 actions after filtering
-        ensure!(output.len() == 2, "Expected 2 actions after filtering, got {}", output.len());
-
-        // Verify the complete order: Urgent priority comes first, then Normal
-        ensure!(
-            output[0].entity_id == "entity_1",
-            "Expected first action to be entity_1, got {}",
-            output[0].entity_id
-        );
-        ensure!(
-            output[0].priority == Priority::Urgent,
-            "Expected first action to have Urgent priority, got {:?}",
-            output[0].priority
-        );
-
-        ensure!(
-            output[1].entity_id == "entity_2",
-            "Expected second action to be entity_2, got {}",
-            output[1].entity_id
-        );
-        ensure!(
-            output[1].priority == Priority::Normal,
-            "Expected second action to have Normal priority, got {:?}",
-            output[1].priority
-        );
+        let body = filter_actions(LambdaEvent::new(json!([]), context))
+            .await
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+        ensure!(body.get() == "[]", "Expected an empty result, got {}", body.get());
 
         Ok(())
     }
 
+    #[cfg(feature = "metrics")]
     #[test]
-    fn test_deduplication_with_priority_conflict() -> Result<()> {
+    fn test_metrics_response_routes_metrics_path() -> Result<()> {
         // ---
-        let input = vec![
-            Action {
-                entity_id: "duplicate".to_string(),
-                last_action_time: parse_date("2025-05-01T00:00:00Z")?,
-                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
-                priority: Priority::Normal,
-            },
-            Action {
-                entity_id: "duplicate".to_string(),
-                last_action_time: parse_date("2025-05-01T00:00:00Z")?,
-                next_action_time: parse_date("2025-07-01T00:00:00Z")?,
-                priority: Priority::Urgent,
-            },
-        ];
-
-        let output = process_actions(input);
-        ensure!(
-            output[0].entity_id == "duplicate",
-            "Expected action to be for entity 'duplicate', got {}",
-            output[0].entity_id
-        );
-
-        // Currently keeps last seen, so should be Urgent
-        ensure!(
-            output[0].priority == Priority::Urgent,
-            "Expected single remaining item to be Urgent"
-        );
+        let ok = metrics_response("GET /metrics HTTP/1.1\r\n");
+        ensure!(ok.starts_with("HTTP/1.1 200 OK"), "Expected 200, got {ok}");
+        ensure!(ok.contains("# TYPE action_filter_processed_total counter"), "Missing metrics");
 
-        Ok(())
-    }
+        let missing = metrics_response("GET / HTTP/1.1\r\n");
+        ensure!(missing.starts_with("HTTP/1.1 404"), "Expected 404, got {missing}");
 
-    #[test]
-    fn test_last_action_time_exactly_7_days() -> Result<()> {
-        // ---
-        let today = Utc::now().date_naive();
-        let input = vec![Action {
-            entity_id: "test".into(),
-            last_action_time: DateTime::<Utc>::from_naive_utc_and_offset(
-                (today - Duration::days(7)).and_hms_opt(0, 0, 0).unwrap(),
-                Utc,
-            ),
-            next_action_time: DateTime::<Utc>::from_naive_utc_and_offset(
-                (today + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap(),
-                Utc,
-            ),
-            priority: Priority::Normal,
-        }];
-
-        let output = process_actions(input);
-
-        // We expect it to be filtered out since it's exactly 7 days ago (not < 7 days)
-        ensure!(output.is_empty(), "Expected action exactly 7 days old to be excluded");
         Ok(())
     }
 
-    #[test]
-    fn test_next_action_time_exactly_90_days() -> Result<()> {
+    #[cfg(feature = "otel")]
+    #[tokio::test]
+    async fn test_otel_layer_builds_from_endpoint() -> Result<()> {
         // ---
-        let today = Utc::now();
-        let input = vec![Action {
-            entity_id: "edge_90_days".to_string(),
-            last_action_time: today - Duration::days(10),
-            next_action_time: today + Duration::days(90),
-            priority: Priority::Normal,
-        }];
-
-        let output = process_actions(input);
-        ensure!(output.len() == 1, "Action 90 days out should be included");
+        let endpoint = Some("http://localhost:4317".to_string());
+        let layer = otel_layer::<tracing_subscriber::Registry>(endpoint)
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+        ensure!(layer.is_some(), "Expected a layer when the endpoint is set");
+
+        let layer = otel_layer::<tracing_subscriber::Registry>(None)
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+        ensure!(layer.is_none(), "Expected no layer without an endpoint");
+
         Ok(())
     }
 }
@@ -0,0 +1,144 @@
+// Process-wide filter counters in Prometheus text format.
+//
+// Every `process_actions` run adds its report to the counters; the binary's optional
+// `metrics` feature serves them on a local `/metrics` endpoint.
+
+use crate::report::FilterReport;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Drop reasons, in `FilterReport` field order; used as the `reason` label
+const DROP_REASONS: [&str; 8] =
+    ["future", "stale", "duplicate", "validation", "denied", "low_score", "sampled_out", "capped"];
+
+/// Running totals across filter runs
+pub(crate) struct Counters {
+    processed: AtomicU64,
+    kept: AtomicU64,
+    dropped: [AtomicU64; DROP_REASONS.len()],
+}
+
+impl Counters {
+    // ---
+    pub(crate) const fn new() -> Self {
+        Counters {
+            processed: AtomicU64::new(0),
+            kept: AtomicU64::new(0),
+            dropped: [const { AtomicU64::new(0) }; DROP_REASONS.len()],
+        }
+    }
+
+    /// Adds one run's tallies to the totals
+    pub(crate) fn record(&self, report: &FilterReport) {
+        // ---
+        let dropped = [
+            report.future,
+            report.stale,
+            report.duplicate,
+            report.validation,
+            report.denied,
+            report.low_score,
+            report.sampled_out,
+            report.capped,
+        ];
+        self.processed.fetch_add(report.input as u64, Ordering::Relaxed);
+        self.kept.fetch_add(report.kept as u64, Ordering::Relaxed);
+        for (counter, count) in self.dropped.iter().zip(dropped) {
+            counter.fetch_add(count as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// The totals in Prometheus text exposition format
+    fn render(&self) -> String {
+        // ---
+        let dropped: Vec<(String, u64)> = DROP_REASONS
+            .iter()
+            .zip(&self.dropped)
+            .map(|(reason, count)| (format!("{{reason=\"{reason}\"}}"), load(count)))
+            .collect();
+
+        let mut text = String::new();
+        family(
+            &mut text,
+            "processed",
+            "Actions received by the filter",
+            &[(String::new(), load(&self.processed))],
+        );
+        family(
+            &mut text,
+            "kept",
+            "Actions returned by the filter",
+            &[(String::new(), load(&self.kept))],
+        );
+        family(&mut text, "dropped", "Actions dropped by the filter, by reason", &dropped);
+        text
+    }
+}
+
+/// Appends one counter family: its HELP and TYPE lines, then a sample per label set
+fn family(text: &mut String, name: &str, help: &str, samples: &[(String, u64)]) {
+    // ---
+    let _ = writeln!(text, "# HELP action_filter_{name}_total {help}");
+    let _ = writeln!(text, "# TYPE action_filter_{name}_total counter");
+    for (labels, value) in samples {
+        let _ = writeln!(text, "action_filter_{name}_total{labels} {value}");
+    }
+}
+
+fn load(counter: &AtomicU64) -> u64 {
+    // ---
+    counter.load(Ordering::Relaxed)
+}
+
+pub(crate) static METRICS: Counters = Counters::new();
+
+/// Totals of every filter run in this process, in Prometheus text format
+pub fn metrics_text() -> String {
+    // ---
+    METRICS.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{ensure, Result};
+
+    #[test]
+    fn test_render_prometheus_text() -> Result<()> {
+        // ---
+        let counters = Counters::new();
+        let report = FilterReport {
+            input: 10,
+            kept: 4,
+            future: 2,
+            stale: 1,
+            duplicate: 3,
+            ..Default::default()
+        };
+        counters.record(&report);
+        counters.record(&FilterReport { input: 1, kept: 1, ..Default::default() });
+
+        let text = counters.render();
+        let expected = "\
+# HELP action_filter_processed_total Actions received by the filter
+# TYPE action_filter_processed_total counter
+action_filter_processed_total 11
+# HELP action_filter_kept_total Actions returned by the filter
+# TYPE action_filter_kept_total counter
+action_filter_kept_total 5
+# HELP action_filter_dropped_total Actions dropped by the filter, by reason
+# TYPE action_filter_dropped_total counter
+action_filter_dropped_total{reason=\"future\"} 2
+action_filter_dropped_total{reason=\"stale\"} 1
+action_filter_dropped_total{reason=\"duplicate\"} 3
+action_filter_dropped_total{reason=\"validation\"} 0
+action_filter_dropped_total{reason=\"denied\"} 0
+action_filter_dropped_total{reason=\"low_score\"} 0
+action_filter_dropped_total{reason=\"sampled_out\"} 0
+action_filter_dropped_total{reason=\"capped\"} 0
+";
+        ensure!(text == expected, "Unexpected metrics text:\n{text}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::domain::Priority;
+use serde::Serialize;
+
+/// Tally of what happened to each input record during `process_actions`
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct FilterReport {
+    /// Number of actions received
+    pub input: usize,
+    /// Number of actions returned
+    pub kept: usize,
+    /// Dropped because next_action_time is beyond the future window
+    pub future: usize,
+    /// Dropped because last_action_time is within the staleness window
+    pub stale: usize,
+    /// Dropped as a duplicate of another action
+    pub duplicate: usize,
+    /// Dropped because the record failed validation
+    pub validation: usize,
+    /// Dropped because the entity_id matched `deny_pattern`
+    pub denied: usize,
+    /// Dropped because the score was below `min_score` (or missing, under the Drop policy)
+    pub low_score: usize,
+    /// Left out by `sample`
+    pub sampled_out: usize,
+    /// Dropped by an output cap after sorting
+    pub capped: usize,
+    /// True when the deadline expired before every record was examined
+    pub truncated: bool,
+    /// Size of the largest group of input actions sharing a next_action_time
+    /// (only computed when `shared_next_time_warn_threshold` is set)
+    pub max_shared_next_time: usize,
+    /// One entry per entity that had duplicates dropped by deduplication
+    pub dedup_audit: Vec<DedupAuditEntry>,
+    /// One entry per surviving-the-filters record whose priority `priority_overrides` changed
+    pub override_audit: Vec<PriorityOverride>,
+}
+
+/// Which occurrences of one duplicated entity survived and which were dropped.
+/// Indices are positions in the decoded input actions.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct DedupAuditEntry {
+    /// entity_id of the surviving occurrence
+    pub entity_id: String,
+    /// Input index of the surviving occurrence
+    pub kept_index: usize,
+    /// Input indices of the dropped occurrences, ascending
+    pub dropped_indices: Vec<usize>,
+}
+
+/// A priority rewritten by `priority_overrides`
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct PriorityOverride {
+    /// entity_id of the overridden record
+    pub entity_id: String,
+    /// Priority the record arrived with
+    pub from: Priority,
+    /// Priority it was given
+    pub to: Priority,
+}
+
+/// What happened to one input occurrence of the entity named by `explain_entity`
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct OccurrenceDecision {
+    /// Position in the decoded input actions
+    pub index: usize,
+    /// "kept", or the drop reason as named in `FilterReport` (e.g. "stale", "duplicate")
+    pub outcome: &'static str,
+    /// For a duplicate, the input index of the occurrence kept instead (when known)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kept_index: Option<usize>,
+}
+
+impl FilterReport {
+    /// Emits the drop-reason counts as a single structured log line
+    pub fn log_summary(&self) {
+        // ---
+        tracing::info!(
+            future = self.future,
+            stale = self.stale,
+            duplicate = self.duplicate,
+            validation = self.validation,
+            denied = self.denied,
+            low_score = self.low_score,
+            sampled_out = self.sampled_out,
+            capped = self.capped,
+            "dropped:"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Timestamp fields of an action that accept offset-less input
+pub(crate) const TIMESTAMP_FIELDS: &[&str] =
+    &["last_action_time", "next_action_time", "created_at"];
+
+/// Stand-in for a next_action_time past chrono's last year: beyond every future window,
+/// yet a timestamp serde can still read back
+const FAR_FUTURE: &str = "9999-12-31T23:59:59+00:00";
+
+/// Parses an RFC 3339 timestamp, or an offset-less `YYYY-MM-DDTHH:MM:SS[.fff]`
+/// interpreted in the `assumed` zone. A bare `YYYY-MM-DD` means midnight in that zone.
+pub(crate) fn parse_timestamp(text: &str, assumed: FixedOffset) -> Option<DateTime<Utc>> {
+    // ---
+    parse_timestamp_at(text, assumed, NaiveTime::MIN)
+}
+
+/// Same as `parse_timestamp`, giving bare dates the `date_time` time of day
+fn parse_timestamp_at(
+    text: &str,
+    assumed: FixedOffset,
+    date_time: NaiveTime,
+) -> Option<DateTime<Utc>> {
+    // ---
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(text) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDate::parse_from_str(text, "%Y-%m-%d").map(|d| d.and_time(date_time)))
+        .ok()?;
+    assumed.from_local_datetime(&naive).single().map(|t| t.with_timezone(&Utc))
+}
+
+/// Rewrites offset-less timestamp strings in a raw action object as RFC 3339 UTC,
+/// interpreting them in the `assumed` zone and giving bare dates the `date_time` time of day.
+/// A well-formed next_action_time past chrono's last year becomes `FAR_FUTURE`, so the
+/// future window drops it. Anything else unparseable is left for serde to reject.
+pub(crate) fn normalize_timestamps(action: &mut Value, assumed: FixedOffset, date_time: NaiveTime) {
+    // ---
+    for field in TIMESTAMP_FIELDS {
+        if let Some(Value::String(text)) = action.get_mut(*field) {
+            if let Some(parsed) = parse_timestamp_at(text, assumed, date_time) {
+                *text = parsed.to_rfc3339();
+            } else if *field == "next_action_time" && beyond_last_year(text, assumed, date_time) {
+                *text = FAR_FUTURE.to_string();
+            }
+        }
+    }
+}
+
+/// True when `text` would parse but for a year later than chrono can represent
+fn beyond_last_year(text: &str, assumed: FixedOffset, date_time: NaiveTime) -> bool {
+    // ---
+    let unsigned = text.strip_prefix('+').unwrap_or(text);
+    let digits = unsigned.find(|c: char| !c.is_ascii_digit()).unwrap_or(unsigned.len());
+    let Ok(year) = unsigned[..digits].parse::<u64>() else {
+        return false;
+    };
+    // A leap year stands in, so a February 29 in the original still parses
+    let in_range = format!("2000{}", &unsigned[digits..]);
+    year > NaiveDate::MAX.year() as u64
+        && parse_timestamp_at(&in_range, assumed, date_time).is_some()
+}
+
+/// `t + delta`, saturating at the representable extremes instead of panicking, so that
+/// window bounds near the end of time degrade to "everything is within range"
+pub(crate) fn saturating_add(t: DateTime<Utc>, delta: Duration) -> DateTime<Utc> {
+    // ---
+    t.checked_add_signed(delta).unwrap_or(if delta < Duration::zero() {
+        DateTime::<Utc>::MIN_UTC
+    } else {
+        DateTime::<Utc>::MAX_UTC
+    })
+}
+
+/// The zero offset used when no zone is assumed
+pub(crate) fn utc() -> FixedOffset {
+    // ---
+    FixedOffset::east_opt(0).expect("zero offset is valid")
+}
+
+/// Serde helper for `DateTime<Utc>` fields that also accepts offset-less input as UTC
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<Utc>, D::Error> {
+    // ---
+    let text = String::deserialize(d)?;
+    parse_timestamp(&text, utc())
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp '{text}'")))
+}
+
+/// Optional variant of `deserialize`
+pub(crate) fn deserialize_opt<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    // ---
+    match Option::<String>::deserialize(d)? {
+        None => Ok(None),
+        Some(text) => parse_timestamp(&text, utc())
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp '{text}'"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{ensure, Result};
+    use serde_json::json;
+
+    #[test]
+    fn test_naive_timestamp_uses_assumed_offset() -> Result<()> {
+        // ---
+        let eastern: FixedOffset = "-05:00".parse()?;
+        let mut action = json!({
+            "last_action_time": "2025-07-01T00:00:00",
+            "next_action_time": "2025-07-02T00:00:00+02:00",
+        });
+
+        normalize_timestamps(&mut action, eastern, NaiveTime::MIN);
+
+        ensure!(
+            action["last_action_time"] == "2025-07-01T05:00:00+00:00",
+            "Expected naive input shifted by the assumed offset, got {}",
+            action["last_action_time"]
+        );
+        ensure!(
+            action["next_action_time"] == "2025-07-01T22:00:00+00:00",
+            "Expected offset-bearing input to keep its own offset, got {}",
+            action["next_action_time"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bare_date_is_midnight() -> Result<()> {
+        // ---
+        #[derive(Deserialize)]
+        struct Stamp {
+            #[serde(deserialize_with = "deserialize")]
+            at: DateTime<Utc>,
+        }
+
+        let stamp: Stamp = serde_json::from_value(json!({ "at": "2025-07-01" }))?;
+        ensure!(stamp.at.to_rfc3339() == "2025-07-01T00:00:00+00:00", "Got {}", stamp.at);
+
+        let mut action = json!({ "next_action_time": "2025-07-01" });
+        normalize_timestamps(
+            &mut action,
+            utc(),
+            NaiveTime::from_hms_opt(9, 30, 0).unwrap_or_default(),
+        );
+        ensure!(
+            action["next_action_time"] == "2025-07-01T09:30:00+00:00",
+            "Expected the configured time of day, got {}",
+            action["next_action_time"]
+        );
+
+        Ok(())
+    }
+}
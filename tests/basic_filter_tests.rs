@@ -3,8 +3,8 @@ use aws_lambda_action_filter::{Action, Priority};
 use serde_json::Value;
 use std::process::Command;
 
-/// Helper function to run cargo lambda invoke and parse the result
-fn run_lambda_invoke(data_file: &str) -> Result<Vec<Action>> {
+/// Helper function to run cargo lambda invoke and return the raw JSON response
+fn run_lambda_invoke_raw(data_file: &str) -> Result<Value> {
     // ---
     let output =
         Command::new("cargo").args(["lambda", "invoke", "--data-file", data_file]).output()?;
@@ -17,26 +17,19 @@ fn run_lambda_invoke(data_file: &str) -> Result<Vec<Action>> {
     );
 
     let stdout = String::from_utf8(output.stdout)?;
+    Ok(serde_json::from_str(&stdout)?)
+}
 
+/// Helper function to run cargo lambda invoke and parse the result
+fn run_lambda_invoke(data_file: &str) -> Result<Vec<Action>> {
+    // ---
     // The output should be a JSON array of actions
-    let json_value: Value = serde_json::from_str(&stdout)?;
+    let json_value = run_lambda_invoke_raw(data_file)?;
     let actions: Vec<Action> = serde_json::from_value(json_value)?;
 
     Ok(actions)
 }
 
-/// Helper function that expects cargo lambda invoke to fail
-fn expect_lambda_invoke_failure(data_file: &str) -> Result<String> {
-    // ---
-    let output =
-        Command::new("cargo").args(["lambda", "invoke", "--data-file", data_file]).output()?;
-
-    ensure!(!output.status.success(), "Expected cargo lambda invoke to fail, but it succeeded");
-
-    let stderr = String::from_utf8(output.stderr)?;
-    Ok(stderr)
-}
-
 #[test]
 fn test_sample_input_integration() -> Result<()> {
     // ---
@@ -148,31 +141,31 @@ fn test_priority_input_integration() -> Result<()> {
 fn test_bad_input_integration() -> Result<()> {
     // ---
     // NOTE: This test file fails during JSON deserialization in our filter_actions callback
-    // at this line: `let input: Vec<Action> = serde_json::from_value(value)?;`
     // because "unknown" is not a valid Priority enum variant. The lambda runtime successfully
-    // passes the JSON to our callback, but we fail when trying to deserialize it into Action structs.
-    //
-    // This DOES test our lambda's error handling, showing that serde deserialization errors
-    // are properly propagated up as lambda errors.
-    let error_output = expect_lambda_invoke_failure("testdata/03_bad-input.json")?;
+    // passes the JSON to our callback, which reports the failure as an RFC 7807 problem+json
+    // body instead of a lambda error.
+    let problem = run_lambda_invoke_raw("testdata/03_bad-input.json")?;
+
+    ensure!(problem["status"] == 400, "Expected problem+json with status 400, got: {}", problem);
 
     // Verify we get the expected deserialization error from our lambda
+    let detail = problem["detail"].as_str().unwrap_or_default();
     ensure!(
-        error_output.contains("unknown variant") && error_output.contains("unknown"),
+        detail.contains("unknown variant") && detail.contains("unknown"),
         "Expected serde deserialization error about 'unknown variant', got: {}",
-        error_output
+        detail
     );
 
     ensure!(
-        error_output.contains("urgent") && error_output.contains("normal"),
+        detail.contains("urgent") && detail.contains("normal"),
         "Expected error to mention valid variants 'urgent' and 'normal', got: {}",
-        error_output
+        detail
     );
 
-    println!("Bad input correctly failed during our lambda's JSON deserialization:");
-    println!("  Error contains 'unknown variant': ✓");
-    println!("  Error mentions valid variants: ✓");
-    println!("  This tests our serde error handling in filter_actions callback");
+    println!("Bad input correctly returned a problem+json body:");
+    println!("  Status is 400: ✓");
+    println!("  Detail contains 'unknown variant': ✓");
+    println!("  Detail mentions valid variants: ✓");
 
     Ok(())
 }
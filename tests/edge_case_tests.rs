@@ -64,6 +64,7 @@ fn create_action(
         last_action_time: now + Duration::days(last_offset),
         next_action_time: now + Duration::days(next_offset),
         priority,
+        ..Default::default()
     }
 }
 